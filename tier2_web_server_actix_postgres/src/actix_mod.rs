@@ -5,26 +5,98 @@ pub type WebForm = actix_web::web::Form<Vec<(String, String)>>;
 pub type WebQuery = actix_web::web::Query<Vec<(String, String)>>;
 pub type ResultResponse = actix_web::Result<actix_web::HttpResponse>;
 pub type DataAppState = actix_web::web::Data<crate::AppState>;
+pub type WebRequest = actix_web::HttpRequest;
+
+/// the same sql functions and views can return html or json
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ResponseFormat {
+    Html,
+    Json,
+}
+
+impl ResponseFormat {
+    /// json is requested with the header Accept: application/json or the route prefix /api/
+    pub fn from_request(req: &WebRequest) -> ResponseFormat {
+        let accept_json = req
+            .headers()
+            .get(actix_web::http::header::ACCEPT)
+            .and_then(|x| x.to_str().ok())
+            .is_some_and(|x| x.contains("application/json"));
+        if accept_json || req.path().contains("/api/") {
+            ResponseFormat::Json
+        } else {
+            ResponseFormat::Html
+        }
+    }
+
+    /// the error is returned in the same format as the data
+    pub fn error(&self, err: crate::error_mod::LibError) -> actix_web::Error {
+        match self {
+            ResponseFormat::Html => err.into(),
+            ResponseFormat::Json => crate::error_mod::JsonLibError(err).into(),
+        }
+    }
+}
 
 /// configure the route with scope
 /// so the routing code is near to the implementation code
-pub fn config_route_main(cfg: &mut actix_web::web::ServiceConfig) {
+/// The crud_scopes have no hand-written module. Their routes are discovered from the sql metadata.
+pub fn config_route_main(
+    cfg: &mut actix_web::web::ServiceConfig,
+    app_state: &crate::AppState,
+    crud_scopes: &[String],
+) {
+    // the css and the login page are public
     cfg.service(actix_files::Files::new(
         "/webpage_hits_admin/css",
-        "./webpage_hits_admin/css/",
+        &app_state.static_root,
     ))
-    .service(
-        actix_web::web::scope("/webpage_hits_admin/webpage_hits")
-            .configure(crate::webpage_hits_mod::config_route_webpage_hits),
-    );
+    .configure(crate::auth_mod::config_route_auth);
+    // the same routes return json with the prefix /api/
+    // every scope requires the logged in user
+    for prefix in ["/webpage_hits_admin", "/webpage_hits_admin/api"] {
+        cfg.service(
+            actix_web::web::scope(&format!("{prefix}/webpage_hits"))
+                .wrap(crate::auth_mod::RequireLogin)
+                .configure(crate::webpage_hits_mod::config_route_webpage_hits),
+        );
+        cfg.service(
+            actix_web::web::scope(&format!("{prefix}/audit_log"))
+                .wrap(crate::auth_mod::RequireLogin)
+                .configure(crate::audit_log_mod::config_route_audit_log),
+        );
+        cfg.service(
+            actix_web::web::scope(&format!("{prefix}/sql_metadata"))
+                .wrap(crate::auth_mod::RequireLogin)
+                .configure(crate::sql_metadata_mod::config_route_sql_metadata),
+        );
+        for scope in crud_scopes {
+            cfg.service(
+                actix_web::web::scope(&format!("{prefix}/{scope}"))
+                    .wrap(crate::auth_mod::RequireLogin)
+                    .configure(|cfg| {
+                        crate::crud_router_mod::config_route_crud(cfg, app_state, scope)
+                    }),
+            );
+        }
+    }
 }
 
 /// fn to return a response when we have the body
 /// web apps modify data all the time, so caching is not good
-pub fn return_response_no_cache(body: String) -> actix_web::Result<actix_web::HttpResponse> {
+pub fn return_response_no_cache(body: String) -> actix_web::HttpResponse {
     use actix_web::http::header;
-    Ok(actix_web::HttpResponse::Ok()
+    actix_web::HttpResponse::Ok()
         .append_header(header::ContentType(mime::TEXT_HTML_UTF_8))
         .append_header(header::CacheControl(vec![header::CacheDirective::NoStore]))
-        .body(body))
+        .body(body)
+}
+
+/// fn to return a json response without cache
+pub fn return_json_response_no_cache(value: serde_json::Value) -> actix_web::HttpResponse {
+    use actix_web::http::header;
+    actix_web::HttpResponse::Ok()
+        .append_header(header::ContentType(mime::APPLICATION_JSON))
+        .append_header(header::CacheControl(vec![header::CacheDirective::NoStore]))
+        .body(value.to_string())
 }
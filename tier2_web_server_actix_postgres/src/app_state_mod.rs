@@ -1,6 +1,9 @@
 // app_state_mod.rs
 
-use crate::postgres_mod::{SqlFunctionInputParams, SqlFunctionInputParamsOrder, SqlViewFields};
+use std::sync::Arc;
+
+use crate::html_templating_mod::HtmlTemplateCache;
+use crate::sql_metadata_mod::SqlMetadataCache;
 
 /// This struct represents state
 /// Every function can extract this simply with an input parameter
@@ -8,7 +11,14 @@ use crate::postgres_mod::{SqlFunctionInputParams, SqlFunctionInputParamsOrder, S
 pub struct AppState {
     pub app_name: String,
     pub db_pool: deadpool_postgres::Pool,
-    pub sql_function_input_params: SqlFunctionInputParams,
-    pub sql_function_input_params_order: SqlFunctionInputParamsOrder,
-    pub sql_view_fields: SqlViewFields,
+    /// functions, views and role permissions, reloaded after DDL changes
+    pub sql_metadata: SqlMetadataCache,
+    /// shared with the error page renderer
+    pub html_templates: Arc<HtmlTemplateCache>,
+    /// NULL values from the database are rendered with this marker
+    pub html_null_marker: String,
+    /// the folder with the css files
+    pub static_root: String,
+    /// the session cookie is sent only over https
+    pub cookie_secure: bool,
 }
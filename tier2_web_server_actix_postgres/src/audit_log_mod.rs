@@ -0,0 +1,40 @@
+//! audit_log_mod.rs
+
+// read-only list of the audit log
+// The rows are inserted by audit_mod after every data modifying call.
+
+use crate::actix_mod::{DataAppState, ResultResponse, WebForm, WebQuery, WebRequest};
+use crate::server_side_multi_row_mod::ServerSideMultiRow;
+use actix_web::web::resource;
+use actix_web::web::to;
+
+const SCOPE: &str = "audit_log";
+/// The where statement is constructed only for existing parameters, because efficiency.
+const AUDIT_LOG_LIST_WHERE_CLAUSE: [&str; 5] = [
+    "user_name like {f_like_user_name}",
+    "function_name like {f_like_function_name}",
+    "outcome = {f_outcome}",
+    "created_at >= {f_from_created_at}",
+    "created_at < {f_to_created_at}",
+];
+
+/// scoped actix routing near the implementation code
+/// scope is already "/webpage_hits_admin/audit_log"
+pub fn config_route_audit_log(cfg: &mut actix_web::web::ServiceConfig) {
+    cfg.service(resource("/audit_log_list").route(to(audit_log_list)));
+}
+
+/// read the audit log with simple filter and order_by, the newest first by default
+#[function_name::named]
+pub async fn audit_log_list(
+    app_state: DataAppState,
+    req: WebRequest,
+    query: WebQuery,
+    form: Option<WebForm>,
+) -> ResultResponse {
+    let mut ssmr =
+        ServerSideMultiRow::new(&app_state, &req, SCOPE, function_name!(), &query, &form)?;
+    ssmr.where_clause = AUDIT_LOG_LIST_WHERE_CLAUSE.to_vec();
+    ssmr.default_order_by = "-id";
+    ssmr.run_multi_row_sql_and_process_html().await
+}
@@ -0,0 +1,120 @@
+//! audit_mod.rs
+
+// Audit log of the data modifying sql functions (insert, update, delete).
+// Every call is recorded with the user, function name, input params and the outcome.
+// The log is written on its own client after the request transaction is finished,
+// so the failed and rolled back calls are also recorded.
+
+use crate::actix_mod::WebRequest;
+use crate::auth_mod::AuthenticatedUser;
+use crate::error_mod::LibError;
+use crate::postgres_mod::{FunctionName, FunctionOverload};
+use crate::postgres_type_mod::PostgresValue;
+use actix_web::HttpMessage;
+
+/// the outcome of the call as text in the table audit_log
+#[derive(strum::Display, Clone, Copy)]
+#[strum(serialize_all = "snake_case")]
+pub enum AuditOutcome {
+    Success,
+    Error,
+    /// the call succeeded, but the transaction was rolled back later
+    RolledBack,
+}
+
+/// one call of a data modifying function, waiting to be written in the audit log
+pub struct AuditCall {
+    pub function_name: FunctionName,
+    /// input params as json object: {"_id": 1, "_webpage": "..."}
+    pub params: serde_json::Value,
+    /// the error of this call, None if the call succeeded
+    pub error_message: Option<String>,
+    /// the call inside an open transaction is rolled back, if the transaction is rolled back later
+    pub in_transaction: bool,
+}
+
+impl AuditCall {
+    /// the params are named in the order of the function input params
+    pub fn new(
+        function_name: &FunctionName,
+        function_overload: Option<&FunctionOverload>,
+        sql_params: &[PostgresValue],
+        result: Result<(), &LibError>,
+        in_transaction: bool,
+    ) -> AuditCall {
+        let params = function_overload
+            .map(|function_overload| {
+                function_overload
+                    .params
+                    .iter()
+                    .zip(sql_params.iter())
+                    .map(|(param, value)| (param.param_name.0.clone(), value.to_json()))
+                    .collect::<serde_json::Map<_, _>>()
+            })
+            .unwrap_or_default();
+        AuditCall {
+            function_name: function_name.clone(),
+            params: serde_json::Value::Object(params),
+            error_message: result.err().map(|err| err.to_string()),
+            in_transaction,
+        }
+    }
+
+    /// the successful call in a transaction is rolled back, if the transaction was rolled back
+    /// The request can still fail after the commit, then the call is a success.
+    pub fn outcome(&self, is_rolled_back: bool) -> AuditOutcome {
+        match (&self.error_message, self.in_transaction && is_rolled_back) {
+            (Some(_), _) => AuditOutcome::Error,
+            (None, true) => AuditOutcome::RolledBack,
+            (None, false) => AuditOutcome::Success,
+        }
+    }
+}
+
+/// write the calls in the table audit_log
+/// The audit log must not change the response of the request, so the errors are only logged.
+pub async fn insert_audit_log(
+    app_state: &crate::AppState,
+    req: &WebRequest,
+    audit_calls: &[AuditCall],
+    is_rolled_back: bool,
+) {
+    if audit_calls.is_empty() {
+        return;
+    }
+    if let Err(err) = try_insert_audit_log(app_state, req, audit_calls, is_rolled_back).await {
+        log::error!("Audit log failed: {err}");
+    }
+}
+
+/// all the calls are written on the same client
+async fn try_insert_audit_log(
+    app_state: &crate::AppState,
+    req: &WebRequest,
+    audit_calls: &[AuditCall],
+    is_rolled_back: bool,
+) -> Result<(), LibError> {
+    let (app_user_id, user_name) = match req.extensions().get::<AuthenticatedUser>() {
+        Some(user) => (Some(user.app_user_id), Some(user.user_name.clone())),
+        None => (None, None),
+    };
+    let query = "SELECT * from audit_log_insert($1, $2, $3, $4, $5, $6);";
+    let postgres_client =
+        crate::deadpool_mod::get_postgres_client_from_pool(&app_state.db_pool).await?;
+    for audit_call in audit_calls {
+        let outcome = audit_call.outcome(is_rolled_back).to_string();
+        let sql_params: [&(dyn tokio_postgres::types::ToSql + Sync); 6] = [
+            &app_user_id,
+            &user_name,
+            &audit_call.function_name.0,
+            &audit_call.params,
+            &outcome,
+            &audit_call.error_message,
+        ];
+        postgres_client
+            .query(query, &sql_params)
+            .await
+            .map_err(|err| crate::postgres_mod::sql_error_to_lib_error(err, query, &sql_params))?;
+    }
+    Ok(())
+}
@@ -0,0 +1,385 @@
+//! auth_mod.rs
+
+// Login for the admin web app.
+// The users and the sessions are in the database tables app_user and app_user_session.
+// The password is verified with the argon2 hash.
+// After the login the random session_id is in the cookie and in the table app_user_session.
+// The middleware RequireLogin wraps every scope. Only the login page and the css are public.
+
+use std::future::{ready, Ready};
+use std::rc::Rc;
+
+use actix_web::body::EitherBody;
+use actix_web::dev::{Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::HttpMessage;
+use futures_util::future::LocalBoxFuture;
+
+use crate::actix_mod::{
+    DataAppState, ResponseFormat, ResultResponse, WebForm, WebQuery, WebRequest,
+};
+use crate::error_mod::LibError;
+use crate::web_params_mod::WebParams;
+
+/// the cookie with the random session_id
+const SESSION_COOKIE_NAME: &str = "session_id";
+/// the user must login again after this time
+const SESSION_HOURS: i32 = 8;
+/// the routes for login and logout are outside of the protected scopes
+const LOGIN_URL: &str = "/webpage_hits_admin/login";
+const LOGOUT_URL: &str = "/webpage_hits_admin/logout";
+/// after login, if there is no valid next url
+const DEFAULT_NEXT_URL: &str = "/webpage_hits_admin/webpage_hits/webpage_hits_list";
+/// the unknown user name is verified against this hash,
+/// so the response time does not reveal which user names exist
+const DUMMY_PASSWORD_HASH: &str =
+    "$argon2id$v=19$m=19456,t=2,p=1$jpV+q/1jIqMcej8OsJdGxQ$InZcAqD9IqoqMz68wVM1n2m1oySy1d29LgZALo6fjao";
+
+/// the logged in user is stored in the request extensions by the middleware
+#[derive(Clone)]
+pub struct AuthenticatedUser {
+    pub app_user_id: i32,
+    pub user_name: String,
+    pub role_names: Vec<String>,
+}
+
+/// public routes for login and logout
+pub fn config_route_auth(cfg: &mut actix_web::web::ServiceConfig) {
+    use actix_web::web::{get, post, resource};
+    cfg.service(
+        resource(LOGIN_URL)
+            .route(get().to(login_form))
+            .route(post().to(login)),
+    )
+    .service(resource(LOGOUT_URL).route(post().to(logout)));
+}
+
+/// argon2 hash in the PHC string format for the table app_user
+/// argon2 is slow on purpose, in async code call it inside actix_web::web::block
+pub fn hash_password(password: &str) -> String {
+    use argon2::password_hash::{rand_core::OsRng, PasswordHasher, SaltString};
+    let salt = SaltString::generate(&mut OsRng);
+    argon2::Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .expect("Cannot hash the password")
+        .to_string()
+}
+
+/// the password is correct if it has the same hash
+fn verify_password(password: &str, password_hash: &str) -> bool {
+    use argon2::password_hash::{PasswordHash, PasswordVerifier};
+    match PasswordHash::new(password_hash) {
+        Ok(parsed_hash) => argon2::Argon2::default()
+            .verify_password(password.as_bytes(), &parsed_hash)
+            .is_ok(),
+        Err(err) => {
+            log::error!("The password hash in app_user is not valid: {err}");
+            false
+        }
+    }
+}
+
+/// UI - login page
+pub async fn login_form(
+    app_state: DataAppState,
+    req: WebRequest,
+    query: WebQuery,
+) -> ResultResponse {
+    let next = query
+        .0
+        .iter()
+        .find(|x| x.0 == "next")
+        .map(|x| x.1.as_str())
+        .unwrap_or(DEFAULT_NEXT_URL);
+    render_login_page(&app_state, &req, next, "", actix_web::http::StatusCode::OK)
+        .map_err(|err| ResponseFormat::from_request(&req).error(err))
+}
+
+/// the template auth/login with the placeholders {next}, {error_message} and {csrf_token}
+fn render_login_page(
+    app_state: &DataAppState,
+    req: &WebRequest,
+    next: &str,
+    error_message: &str,
+    status_code: actix_web::http::StatusCode,
+) -> Result<actix_web::HttpResponse, LibError> {
+    let template = app_state.html_templates.get("auth", "login")?;
+    let body = template
+        .text
+        .replace("{next}", &html_escape::encode_double_quoted_attribute(next))
+        .replace("{error_message}", &html_escape::encode_text(error_message))
+        .replace("{csrf_token}", &crate::csrf_mod::csrf_token(req));
+    let mut response = crate::actix_mod::return_response_no_cache(body);
+    *response.status_mut() = status_code;
+    Ok(response)
+}
+
+/// verify the user and password, create the session and redirect to the next url
+pub async fn login(
+    app_state: DataAppState,
+    req: WebRequest,
+    query: WebQuery,
+    form: Option<WebForm>,
+) -> ResultResponse {
+    let response_format = ResponseFormat::from_request(&req);
+    // login needs the csrf token, so another site cannot login the user with its own account
+    let web_params = WebParams::from_actix(&req, &query, &form, true)
+        .map_err(|err| response_format.error(err))?;
+    let next = web_params
+        .0
+        .get("next")
+        .map(|x| x.as_str())
+        .filter(|x| is_local_url(x))
+        .unwrap_or(DEFAULT_NEXT_URL);
+    let app_user_id = verify_login(&app_state, &web_params)
+        .await
+        .map_err(|err| response_format.error(err))?;
+    let Some(app_user_id) = app_user_id else {
+        if response_format == ResponseFormat::Json {
+            return Err(response_format.error(LibError::Unauthorized {
+                user_friendly: "Wrong user name or password.".to_string(),
+                developer_friendly: format!("{:?}", web_params.0.get("user_name")),
+                source_line_column: format!("{}:{}:{}", file!(), line!(), column!()),
+            }));
+        }
+        return render_login_page(
+            &app_state,
+            &req,
+            next,
+            "Wrong user name or password.",
+            actix_web::http::StatusCode::UNAUTHORIZED,
+        )
+        .map_err(|err| response_format.error(err));
+    };
+
+    let session_id = crate::csrf_mod::random_token();
+    crate::postgres_mod::run_sql_select_query_pool(
+        &app_state.db_pool,
+        "SELECT * from app_user_session_insert($1, $2, $3);",
+        &[&session_id, &app_user_id, &SESSION_HOURS],
+    )
+    .await
+    .map_err(|err| response_format.error(err))?;
+
+    let cookie = actix_web::cookie::Cookie::build(SESSION_COOKIE_NAME, session_id)
+        .path("/")
+        .http_only(true)
+        .secure(app_state.cookie_secure)
+        .same_site(actix_web::cookie::SameSite::Lax)
+        .max_age(actix_web::cookie::time::Duration::hours(
+            SESSION_HOURS.into(),
+        ))
+        .finish();
+    Ok(actix_web::HttpResponse::SeeOther()
+        .append_header((actix_web::http::header::LOCATION, next))
+        .cookie(cookie)
+        .finish())
+}
+
+/// returns the app_user_id if the user name and password are correct
+async fn verify_login(
+    app_state: &DataAppState,
+    web_params: &WebParams,
+) -> Result<Option<i32>, LibError> {
+    let user_name = web_params.get_str("user_name")?;
+    let password = web_params.get_str("password")?;
+    let rows = crate::postgres_mod::run_sql_select_query_pool(
+        &app_state.db_pool,
+        "SELECT id, password_hash from app_user_login_get($1);",
+        &[&user_name],
+    )
+    .await?;
+    let (app_user_id, password_hash): (Option<i32>, String) = match rows.first() {
+        Some(row) => (Some(row.get(0)), row.get(1)),
+        None => (None, DUMMY_PASSWORD_HASH.to_string()),
+    };
+    // argon2 is slow on purpose and must not block the async worker thread
+    let password = password.to_string();
+    let is_verified = actix_web::web::block(move || verify_password(&password, &password_hash))
+        .await
+        .map_err(|err| LibError::BlockingTask {
+            user_friendly: "verify_password".to_string(),
+            developer_friendly: format!("{err}"),
+            source_line_column: format!("{}:{}:{}", file!(), line!(), column!()),
+        })?;
+    Ok(app_user_id.filter(|_| is_verified))
+}
+
+/// delete the session and the cookie
+pub async fn logout(
+    app_state: DataAppState,
+    req: WebRequest,
+    query: WebQuery,
+    form: Option<WebForm>,
+) -> ResultResponse {
+    let response_format = ResponseFormat::from_request(&req);
+    WebParams::from_actix(&req, &query, &form, true).map_err(|err| response_format.error(err))?;
+    if let Some(cookie) = req.cookie(SESSION_COOKIE_NAME) {
+        crate::postgres_mod::run_sql_select_query_pool(
+            &app_state.db_pool,
+            "SELECT * from app_user_session_delete($1);",
+            &[&cookie.value()],
+        )
+        .await
+        .map_err(|err| response_format.error(err))?;
+    }
+    let mut cookie = actix_web::cookie::Cookie::build(SESSION_COOKIE_NAME, "")
+        .path("/")
+        .secure(app_state.cookie_secure)
+        .finish();
+    cookie.make_removal();
+    Ok(actix_web::HttpResponse::SeeOther()
+        .append_header((actix_web::http::header::LOCATION, LOGIN_URL))
+        .cookie(cookie)
+        .finish())
+}
+
+/// redirect only inside this web app, never to another site
+pub fn is_local_url(url: &str) -> bool {
+    url.starts_with("/webpage_hits_admin/") && !url.starts_with("//")
+}
+
+/// the user of the valid session in the cookie
+async fn authenticated_user(req: &WebRequest) -> Result<Option<AuthenticatedUser>, LibError> {
+    let Some(cookie) = req.cookie(SESSION_COOKIE_NAME) else {
+        return Ok(None);
+    };
+    let Some(app_state) = req.app_data::<DataAppState>() else {
+        return Ok(None);
+    };
+    let rows = crate::postgres_mod::run_sql_select_query_pool(
+        &app_state.db_pool,
+        "SELECT app_user_id, user_name, role_names from app_user_session_get($1);",
+        &[&cookie.value()],
+    )
+    .await?;
+    Ok(rows.first().map(|row| AuthenticatedUser {
+        app_user_id: row.get(0),
+        user_name: row.get(1),
+        role_names: row.get(2),
+    }))
+}
+
+/// the id of the logged in user for the row-level security in postgres
+pub fn app_user_id(req: &WebRequest) -> Option<i32> {
+    req.extensions()
+        .get::<AuthenticatedUser>()
+        .map(|x| x.app_user_id)
+}
+
+/// the role of the logged in user must allow the function or view
+/// the object_name "*" allows all functions and views
+pub fn check_permission(
+    app_state: &crate::AppState,
+    req: &WebRequest,
+    object_name: &str,
+) -> Result<(), LibError> {
+    let extensions = req.extensions();
+    let user_name = match extensions.get::<AuthenticatedUser>() {
+        Some(user) => {
+            let sql_metadata = app_state.sql_metadata.get();
+            let is_permitted = user.role_names.iter().any(|role_name| {
+                sql_metadata
+                    .role_permissions
+                    .get(role_name)
+                    .is_some_and(|x| x.contains(object_name) || x.contains("*"))
+            });
+            if is_permitted {
+                return Ok(());
+            }
+            user.user_name.as_str()
+        }
+        // the route is not protected by RequireLogin
+        None => "",
+    };
+    Err(LibError::Forbidden {
+        user_friendly: format!("You don't have the permission for {object_name}."),
+        developer_friendly: format!("user: {user_name}, object_name: {object_name}"),
+        source_line_column: format!("{}:{}:{}", file!(), line!(), column!()),
+    })
+}
+
+/// the browser is redirected to the login page, the json client gets 401 Unauthorized
+fn login_required_response(req: &WebRequest) -> actix_web::HttpResponse {
+    let err = LibError::Unauthorized {
+        user_friendly: "Login is required.".to_string(),
+        developer_friendly: req.path().to_string(),
+        source_line_column: format!("{}:{}:{}", file!(), line!(), column!()),
+    };
+    match ResponseFormat::from_request(req) {
+        ResponseFormat::Json => {
+            actix_web::ResponseError::error_response(&crate::error_mod::JsonLibError(err))
+        }
+        ResponseFormat::Html => {
+            let next = match req.query_string() {
+                "" => req.path().to_string(),
+                query_string => format!("{}?{}", req.path(), query_string),
+            };
+            let query = serde_urlencoded::to_string([("next", next)]).unwrap_or_default();
+            actix_web::HttpResponse::SeeOther()
+                .append_header((
+                    actix_web::http::header::LOCATION,
+                    format!("{LOGIN_URL}?{query}"),
+                ))
+                .finish()
+        }
+    }
+}
+
+/// middleware that allows only the requests with a valid session
+/// the AuthenticatedUser is stored in the request extensions
+pub struct RequireLogin;
+
+impl<S, B> Transform<S, ServiceRequest> for RequireLogin
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = actix_web::Error;
+    type Transform = RequireLoginMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RequireLoginMiddleware {
+            service: Rc::new(service),
+        }))
+    }
+}
+
+/// the service created by RequireLogin
+pub struct RequireLoginMiddleware<S> {
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for RequireLoginMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = actix_web::Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    actix_web::dev::forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = self.service.clone();
+        Box::pin(async move {
+            let (http_req, payload) = req.into_parts();
+            match authenticated_user(&http_req).await {
+                Ok(Some(user)) => {
+                    http_req.extensions_mut().insert(user);
+                    let req = ServiceRequest::from_parts(http_req, payload);
+                    let res = service.call(req).await?;
+                    Ok(res.map_into_left_body())
+                }
+                Ok(None) => {
+                    let response = login_required_response(&http_req);
+                    Ok(ServiceResponse::new(http_req, response).map_into_right_body())
+                }
+                Err(err) => Err(ResponseFormat::from_request(&http_req).error(err)),
+            }
+        })
+    }
+}
@@ -1,47 +1,107 @@
 // refactoring_database_web_ui_server_side_rendering/tier2_web_server_actix_postgres/src/bin/webpage_hits_admin/main.rs
+use clap::Parser;
 use tier2_web_server_actix_postgres as tier2;
 
 /// the binary executable entry point
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
+    // this loads our .env file and includes the values in std::env
+    // before the logger, so RUST_LOG can be defined in the .env file
+    dotenv::dotenv().ok();
     pretty_env_logger::init();
+    let cli = tier2::Cli::parse();
 
-    println!("Actix web server started on localhost:8080!");
+    // helper for the administrator to create the password hash for a new user in app_user
+    // echo -n "password" | webpage_hits_admin hash_password
+    if let Some(tier2::CliCommand::HashPassword) = cli.command {
+        let mut password = String::new();
+        std::io::stdin().read_line(&mut password)?;
+        // argon2 is slow on purpose, it runs on the thread pool for blocking code
+        let password_hash = actix_web::web::block(move || {
+            tier2::hash_password(password.trim_end_matches(['\r', '\n']))
+        })
+        .await
+        .map_err(std::io::Error::other)?;
+        println!("{password_hash}");
+        return Ok(());
+    }
+
+    // all the configuration errors are printed together and the server does not start
+    let config = match tier2::AppConfig::load(&cli) {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("{err}");
+            std::process::exit(1);
+        }
+    };
+    log::info!("{config:?}");
+
+    println!(
+        "Actix web server started on {}:{}!",
+        config.server.bind_address, config.server.port
+    );
     println!("Test it with curl or browser:");
-    println!("http://localhost:8080/webpage_hits_admin/webpage_hits/webpage_hits_list");
+    println!(
+        "http://localhost:{}/webpage_hits_admin/webpage_hits/webpage_hits_list",
+        config.server.port
+    );
 
     // connection pool for postgres to reuse connections for better performance
-    let db_pool = tier2::deadpool_start_and_check().await;
+    let db_pool = tier2::deadpool_start_and_check(&config.pg).await;
+
+    // on start get all the input parameters for sql functions, the view fields and the permissions.
+    // The cache is reloaded without restart, when the sql objects are modified.
+    let sql_metadata = tier2::SqlMetadata::load(&db_pool)
+        .await
+        .expect("Cannot read the sql metadata");
 
-    // on start get all the input parameters for sql functions.
-    // So I can parse string params to a correct rust data type.
-    let (sql_function_input_params, sql_function_input_params_order) =
-        tier2::get_for_cache_all_function_input_params(&db_pool).await;
+    // html templates are read and parsed once on start
+    // In hot reload mode (for development) the modified templates are read again.
+    let html_templates = std::sync::Arc::new(
+        tier2::HtmlTemplateCache::load_all(&config.server.template_root, config.html.hot_reload)
+            .expect("Cannot read html templates"),
+    );
+    // errors are rendered as html pages inside the admin UI
+    if let Err(err) = html_templates.get_by_key(&config.html.error_template) {
+        println!("Error page is plain text: {err}");
+    }
+    tier2::set_error_template(html_templates.clone(), config.html.error_template.clone());
 
-    // I need the view fields and types to construct the WHERE clause
-    let sql_view_fields = tier2::get_for_cache_all_view_fields(&db_pool).await;
+    // scopes without a hand-written module: the routes are discovered from the sql metadata
+    let crud_scopes = config.crud_scopes.clone();
 
     // Create web::Data outside of closure HttpServer::new.
     let app_state = actix_web::web::Data::new(tier2::AppState {
-        app_name: String::from("bestia.dev"),
-        db_pool: db_pool,
-        sql_function_input_params,
-        sql_function_input_params_order,
-        sql_view_fields,
+        app_name: config.app_name.clone(),
+        db_pool,
+        sql_metadata: tier2::SqlMetadataCache::new(sql_metadata),
+        html_templates,
+        html_null_marker: config.html.null_marker.clone(),
+        static_root: config.server.static_root.clone(),
+        cookie_secure: config.server.cookie_secure,
     });
 
-    let http_server_result = actix_web::HttpServer::new(move || {
+    // the event trigger in postgres notifies the DDL changes
+    tier2::spawn_sql_metadata_listener(app_state.clone(), &config.pg);
+
+    let mut http_server = actix_web::HttpServer::new(move || {
         actix_web::App::new()
             // app_data is cloned for every worker thread
             .app_data(app_state.clone())
+            // the csrf cookie for every browser session
+            .wrap_fn(tier2::csrf_cookie_wrap)
             // the route is configured near the implementation code
-            .configure(tier2::config_route_main)
-    })
-    .bind(("0.0.0.0", 8080))?
-    .run()
-    .await;
+            .configure(|cfg| tier2::config_route_main(cfg, &app_state, &crud_scopes))
+    });
+    if let Some(workers) = config.server.workers {
+        http_server = http_server.workers(workers);
+    }
+    let http_server_result = http_server
+        .bind((config.server.bind_address.as_str(), config.server.port))?
+        .run()
+        .await;
 
-    println!("");
+    println!();
     println!("Actix web server stopped!");
     // return
     http_server_result
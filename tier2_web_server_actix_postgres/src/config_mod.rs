@@ -0,0 +1,433 @@
+//! config_mod.rs
+
+// Typed configuration of the webpage_hits_admin binary.
+// The values are read in this order, the later overrides the former:
+// 1. default values
+// 2. config file in toml format: --config webpage_hits_admin.toml
+// 3. environment variables (also from the .env file): PG.HOST=localhost
+// 4. command line arguments: --pg-host localhost
+// The keys are the same in all sources. The toml table [pg.pool] with max_size is PG.POOL.MAX_SIZE.
+// All the validation errors are collected and returned together.
+
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::error_mod::LibError;
+
+/// the config file is optional, if it does not exist in the default location
+const DEFAULT_CONFIG_FILE: &str = "webpage_hits_admin.toml";
+
+/// command line arguments override the config file and the environment variables
+#[derive(clap::Parser, Debug)]
+#[command(version, about = "Admin web app for the webpage hit counter")]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<CliCommand>,
+    /// config file in toml format, default: webpage_hits_admin.toml if it exists
+    #[arg(long)]
+    pub config: Option<String>,
+    /// bind address of the http server: SERVER.BIND_ADDRESS
+    #[arg(long)]
+    pub bind_address: Option<String>,
+    /// port of the http server: SERVER.PORT
+    #[arg(long)]
+    pub port: Option<String>,
+    /// number of worker threads: SERVER.WORKERS
+    #[arg(long)]
+    pub workers: Option<String>,
+    /// folder with the html templates: SERVER.TEMPLATE_ROOT
+    #[arg(long)]
+    pub template_root: Option<String>,
+    /// folder with the css files: SERVER.STATIC_ROOT
+    #[arg(long)]
+    pub static_root: Option<String>,
+    /// postgres host: PG.HOST
+    #[arg(long)]
+    pub pg_host: Option<String>,
+    /// postgres port: PG.PORT
+    #[arg(long)]
+    pub pg_port: Option<String>,
+    /// postgres user: PG.USER
+    #[arg(long)]
+    pub pg_user: Option<String>,
+    /// postgres database: PG.DBNAME
+    #[arg(long)]
+    pub pg_dbname: Option<String>,
+    /// max number of connections in the pool: PG.POOL.MAX_SIZE
+    #[arg(long)]
+    pub pg_pool_max_size: Option<String>,
+}
+
+/// commands that do not start the web server
+#[derive(clap::Subcommand, Debug)]
+pub enum CliCommand {
+    /// read the password from stdin and print the argon2 hash for the table app_user
+    ///
+    /// echo -n "password" | webpage_hits_admin hash_password
+    #[command(name = "hash_password")]
+    HashPassword,
+}
+
+impl Cli {
+    /// the arguments with the same keys as the environment variables
+    fn to_key_values(&self) -> Vec<(&'static str, &Option<String>)> {
+        vec![
+            ("SERVER.BIND_ADDRESS", &self.bind_address),
+            ("SERVER.PORT", &self.port),
+            ("SERVER.WORKERS", &self.workers),
+            ("SERVER.TEMPLATE_ROOT", &self.template_root),
+            ("SERVER.STATIC_ROOT", &self.static_root),
+            ("PG.HOST", &self.pg_host),
+            ("PG.PORT", &self.pg_port),
+            ("PG.USER", &self.pg_user),
+            ("PG.DBNAME", &self.pg_dbname),
+            ("PG.POOL.MAX_SIZE", &self.pg_pool_max_size),
+        ]
+    }
+}
+
+/// the configuration of the whole app
+#[derive(Debug, Clone)]
+pub struct AppConfig {
+    pub app_name: String,
+    pub server: ServerConfig,
+    pub html: HtmlConfig,
+    /// scopes without a hand-written module: the routes are discovered from the sql metadata
+    pub crud_scopes: Vec<String>,
+    pub pg: PgConfig,
+}
+
+/// the http server
+#[derive(Debug, Clone)]
+pub struct ServerConfig {
+    pub bind_address: String,
+    pub port: u16,
+    /// None is the number of physical cpus
+    pub workers: Option<usize>,
+    /// the folder with the html templates
+    pub template_root: String,
+    /// the folder with the css files
+    pub static_root: String,
+    /// the session cookie is sent only over https, false only for development over http
+    pub cookie_secure: bool,
+}
+
+/// the rendering of the html templates
+#[derive(Debug, Clone)]
+pub struct HtmlConfig {
+    /// the modified templates are read again (for development)
+    pub hot_reload: bool,
+    /// NULL values from the database are rendered with this marker
+    pub null_marker: String,
+    /// errors are rendered as html pages inside the admin UI
+    pub error_template: String,
+}
+
+/// the connection to postgres and the connection pool
+#[derive(Debug, Clone)]
+pub struct PgConfig {
+    /// host, port, user, password, dbname and sslmode
+    /// The Debug of tokio_postgres::Config never prints the password.
+    pub connection: tokio_postgres::Config,
+    /// pem file with the CA certificates for TLS, else the webpki root certificates are used
+    pub ssl_root_cert: Option<SslRootCert>,
+    pub pool_max_size: usize,
+    pub pool_wait_timeout: Option<Duration>,
+    pub pool_create_timeout: Option<Duration>,
+    pub pool_recycle_timeout: Option<Duration>,
+}
+
+/// the CA certificates from the pem file are read and validated with the config
+#[derive(Clone)]
+pub struct SslRootCert {
+    pub path: String,
+    pub root_store: rustls::RootCertStore,
+}
+
+/// only the path and the number of certificates
+impl std::fmt::Debug for SslRootCert {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SslRootCert")
+            .field("path", &self.path)
+            .field("certificates", &self.root_store.len())
+            .finish()
+    }
+}
+
+impl AppConfig {
+    /// read and validate the config from all the sources
+    /// The environment variables from the .env file must be loaded before.
+    pub fn load(cli: &Cli) -> Result<AppConfig, LibError> {
+        let mut values = read_config_file(cli.config.as_deref())?;
+        // the empty value in the .env file does not override the config file
+        // std::env::vars() panics on the variables that are not valid unicode
+        let mut errors = vec![];
+        for (key, value) in std::env::vars_os() {
+            let Ok(key) = key.into_string() else {
+                continue;
+            };
+            if key != key.to_uppercase() || !key.contains('.') {
+                continue;
+            }
+            match value.into_string() {
+                Ok(value) if value.is_empty() => {}
+                Ok(value) => {
+                    values.insert(key, value);
+                }
+                Err(_value) => errors.push(format!("{key} is not valid unicode")),
+            }
+        }
+        for (key, value) in cli.to_key_values() {
+            if let Some(value) = value {
+                values.insert(key.to_string(), value.clone());
+            }
+        }
+        let mut reader = ConfigReader { values, errors };
+        let config = AppConfig::from_reader(&mut reader);
+        if reader.errors.is_empty() {
+            Ok(config)
+        } else {
+            Err(LibError::Config {
+                user_friendly: reader.errors.join("\n"),
+                developer_friendly: format!("config file: {:?}", cli.config),
+                source_line_column: format!("{}:{}:{}", file!(), line!(), column!()),
+            })
+        }
+    }
+
+    /// the default value is used, if the key does not exist in any source
+    fn from_reader(reader: &mut ConfigReader) -> AppConfig {
+        AppConfig {
+            app_name: reader.get("APP.NAME", "bestia.dev".to_string()),
+            server: ServerConfig {
+                bind_address: reader.get("SERVER.BIND_ADDRESS", "0.0.0.0".to_string()),
+                port: reader.get("SERVER.PORT", 8080),
+                workers: reader.get_optional("SERVER.WORKERS"),
+                template_root: reader.get("SERVER.TEMPLATE_ROOT", "webpage_hits_admin".to_string()),
+                static_root: reader.get("SERVER.STATIC_ROOT", "webpage_hits_admin/css".to_string()),
+                cookie_secure: reader.get("SERVER.COOKIE_SECURE", true),
+            },
+            html: HtmlConfig {
+                hot_reload: reader.get("HTML.HOT_RELOAD", false),
+                null_marker: reader.get("HTML.NULL_MARKER", String::new()),
+                error_template: reader.get("HTML.ERROR_TEMPLATE", "error/error_page".to_string()),
+            },
+            crud_scopes: reader
+                .get("CRUD.SCOPES", String::new())
+                .split(',')
+                .map(|x| x.trim().to_string())
+                .filter(|x| !x.is_empty())
+                .collect(),
+            pg: PgConfig {
+                connection: reader.get_pg_connection(),
+                ssl_root_cert: reader.get_ssl_root_cert("PG.SSL_ROOT_CERT"),
+                pool_max_size: reader.get_positive("PG.POOL.MAX_SIZE", 16),
+                pool_wait_timeout: reader.get_seconds("PG.POOL.WAIT_TIMEOUT_SECS"),
+                pool_create_timeout: reader.get_seconds("PG.POOL.CREATE_TIMEOUT_SECS"),
+                pool_recycle_timeout: reader.get_seconds("PG.POOL.RECYCLE_TIMEOUT_SECS"),
+            },
+        }
+    }
+}
+
+/// the config file is flattened to the same keys as the environment variables
+fn read_config_file(config_file: Option<&str>) -> Result<HashMap<String, String>, LibError> {
+    let path = config_file.unwrap_or(DEFAULT_CONFIG_FILE);
+    let text = match std::fs::read_to_string(path) {
+        Ok(text) => text,
+        // only the default config file is optional
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound && config_file.is_none() => {
+            return Ok(HashMap::new())
+        }
+        Err(err) => {
+            return Err(LibError::Config {
+                user_friendly: format!("Cannot read the config file {path}: {err}"),
+                developer_friendly: String::new(),
+                source_line_column: format!("{}:{}:{}", file!(), line!(), column!()),
+            })
+        }
+    };
+    let table: toml::Table = toml::from_str(&text).map_err(|err| LibError::Config {
+        user_friendly: format!("The config file {path} is not valid toml: {err}"),
+        developer_friendly: String::new(),
+        source_line_column: format!("{}:{}:{}", file!(), line!(), column!()),
+    })?;
+    let mut values = HashMap::new();
+    flatten_toml_table("", &table, &mut values);
+    Ok(values)
+}
+
+/// [pg.pool] max_size = 16 becomes PG.POOL.MAX_SIZE=16
+/// arrays become comma separated lists
+fn flatten_toml_table(prefix: &str, table: &toml::Table, values: &mut HashMap<String, String>) {
+    for (key, value) in table {
+        let key = format!("{prefix}{}", key.to_uppercase());
+        let text = match value {
+            toml::Value::Table(table) => {
+                flatten_toml_table(&format!("{key}."), table, values);
+                continue;
+            }
+            toml::Value::String(text) => text.clone(),
+            toml::Value::Array(array) => array
+                .iter()
+                .map(|x| match x {
+                    toml::Value::String(text) => text.clone(),
+                    x => x.to_string(),
+                })
+                .collect::<Vec<_>>()
+                .join(","),
+            value => value.to_string(),
+        };
+        values.insert(key, text);
+    }
+}
+
+/// reads typed values and collects the validation errors
+struct ConfigReader {
+    values: HashMap<String, String>,
+    errors: Vec<String>,
+}
+
+impl ConfigReader {
+    /// the empty string is the same as a missing value
+    fn get_optional<T: FromStr>(&mut self, key: &str) -> Option<T>
+    where
+        T::Err: std::fmt::Display,
+    {
+        let text = self.values.get(key).filter(|x| !x.trim().is_empty())?;
+        match text.trim().parse() {
+            Ok(value) => Some(value),
+            Err(err) => {
+                self.errors
+                    .push(format!("{key}={text} is not valid: {err}"));
+                None
+            }
+        }
+    }
+
+    fn get<T: FromStr>(&mut self, key: &str, default: T) -> T
+    where
+        T::Err: std::fmt::Display,
+    {
+        self.get_optional(key).unwrap_or(default)
+    }
+
+    fn get_required(&mut self, key: &str) -> String {
+        self.get_optional(key).unwrap_or_else(|| {
+            self.errors.push(format!("{key} is required"));
+            String::new()
+        })
+    }
+
+    /// the pem file must exist and all the certificates in it must be valid
+    fn get_ssl_root_cert(&mut self, key: &str) -> Option<SslRootCert> {
+        let path: String = self.get_optional(key)?;
+        let certs = std::fs::File::open(&path)
+            .and_then(|file| rustls_pemfile::certs(&mut std::io::BufReader::new(file)));
+        let certs = match certs {
+            Ok(certs) => certs,
+            Err(err) => {
+                self.errors
+                    .push(format!("{key}={path} cannot be read: {err}"));
+                return None;
+            }
+        };
+        let mut root_store = rustls::RootCertStore::empty();
+        let (added, ignored) = root_store.add_parsable_certificates(&certs);
+        if added == 0 || ignored > 0 {
+            self.errors.push(format!(
+                "{key}={path} has {added} valid and {ignored} invalid certificates"
+            ));
+            return None;
+        }
+        Some(SslRootCert { path, root_store })
+    }
+
+    /// PG.URL=postgres://user@host:port/dbname?sslmode=require
+    /// replaces PG.HOST, PG.PORT, PG.USER and PG.DBNAME.
+    /// PG.PASSWORD, PG.PASSWORD_FILE and PG.SSLMODE override the values in the url.
+    fn get_pg_connection(&mut self) -> tokio_postgres::Config {
+        // the url can contain the password, so it is never printed in the error
+        let url = self.values.get("PG.URL").filter(|x| !x.trim().is_empty());
+        let mut connection = match url.map(|x| x.trim().parse::<tokio_postgres::Config>()) {
+            Some(Ok(connection)) => {
+                if connection.get_user().is_none() || connection.get_dbname().is_none() {
+                    self.errors
+                        .push("PG.URL must contain the user and the dbname".to_string());
+                }
+                connection
+            }
+            Some(Err(err)) => {
+                self.errors.push(format!("PG.URL is not valid: {err}"));
+                tokio_postgres::Config::new()
+            }
+            None => {
+                let mut connection = tokio_postgres::Config::new();
+                connection.host(self.get_required("PG.HOST").as_str());
+                connection.port(self.get("PG.PORT", 5432));
+                connection.user(self.get_required("PG.USER").as_str());
+                connection.dbname(self.get_required("PG.DBNAME").as_str());
+                // without TLS like before, unless PG.SSLMODE says otherwise
+                connection.ssl_mode(tokio_postgres::config::SslMode::Disable);
+                connection
+            }
+        };
+        if let Some(password) = self.get_pg_password() {
+            connection.password(password);
+        }
+        if let Some(ssl_mode) = self.get_optional::<String>("PG.SSLMODE") {
+            match ssl_mode.as_str() {
+                "disable" => connection.ssl_mode(tokio_postgres::config::SslMode::Disable),
+                "prefer" => connection.ssl_mode(tokio_postgres::config::SslMode::Prefer),
+                "require" => connection.ssl_mode(tokio_postgres::config::SslMode::Require),
+                _ => {
+                    self.errors.push(format!(
+                        "PG.SSLMODE={ssl_mode} is not valid: use disable, prefer or require"
+                    ));
+                    &mut connection
+                }
+            };
+        }
+        connection
+    }
+
+    /// the password from the secrets file is better than in the environment variable
+    fn get_pg_password(&mut self) -> Option<String> {
+        // the spaces are valid in the password
+        let password = self
+            .values
+            .get("PG.PASSWORD")
+            .filter(|x| !x.is_empty())
+            .cloned();
+        let Some(password_file) = self.get_optional::<String>("PG.PASSWORD_FILE") else {
+            return password;
+        };
+        if password.is_some() {
+            self.errors
+                .push("Only one of PG.PASSWORD and PG.PASSWORD_FILE can be used".to_string());
+        }
+        match std::fs::read_to_string(&password_file) {
+            // the trailing new line is not part of the password
+            Ok(text) => Some(text.trim_end_matches(['\r', '\n']).to_string()),
+            Err(err) => {
+                self.errors.push(format!(
+                    "PG.PASSWORD_FILE={password_file} cannot be read: {err}"
+                ));
+                None
+            }
+        }
+    }
+
+    fn get_positive(&mut self, key: &str, default: usize) -> usize {
+        let value = self.get(key, default);
+        if value == 0 {
+            self.errors.push(format!("{key} must be greater than 0"));
+        }
+        value
+    }
+
+    fn get_seconds(&mut self, key: &str) -> Option<Duration> {
+        self.get_optional(key).map(Duration::from_secs)
+    }
+}
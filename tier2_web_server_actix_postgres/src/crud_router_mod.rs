@@ -0,0 +1,149 @@
+//! crud_router_mod.rs
+
+// Generic routes for CRUD without a hand-written module like webpage_hits_mod.
+// For a configured scope the sql objects are discovered from the metadata in AppState:
+// the view {scope}_list and the functions {scope}_new, _edit, _insert, _show, _update, _delete.
+// The list has also the export and the insert function has also the csv import.
+// Adding a new table requires only sql objects and html templates in the folder {scope}.
+
+use crate::actix_mod::{DataAppState, WebForm, WebQuery, WebRequest};
+use crate::app_state_mod::AppState;
+use crate::postgres_mod::{FunctionName, ViewName};
+use crate::server_side_import_mod::ServerSideImport;
+use crate::server_side_multi_row_mod::ServerSideMultiRow;
+use crate::server_side_single_row_mod::ServerSideSingleRow;
+use actix_web::web::{get, post, resource, to};
+
+/// the suffixes of sql functions that return a single row
+const SINGLE_ROW_SUFFIXES: [&str; 6] = ["new", "edit", "insert", "show", "update", "delete"];
+
+/// actix routing for the discovered sql objects
+/// scope is already "/webpage_hits_admin/{scope}"
+pub fn config_route_crud(
+    cfg: &mut actix_web::web::ServiceConfig,
+    app_state: &AppState,
+    scope: &str,
+) {
+    // the routes are discovered once on start
+    let sql_metadata = app_state.sql_metadata.get();
+    let view_name = format!("{scope}_list");
+    if sql_metadata
+        .view_fields
+        .contains_key(&ViewName(view_name.clone()))
+    {
+        cfg.service(resource(format!("/{view_name}")).route(route_multi_row(scope, &view_name)));
+        cfg.service(
+            resource(format!("/{view_name}_export")).route(route_export(scope, &view_name)),
+        );
+    }
+    for suffix in SINGLE_ROW_SUFFIXES {
+        let function_name = format!("{scope}_{suffix}");
+        if sql_metadata
+            .function_overloads
+            .contains_key(&FunctionName(function_name.clone()))
+        {
+            cfg.service(
+                resource(format!("/{function_name}"))
+                    .route(route_single_row(scope, &function_name)),
+            );
+        }
+    }
+    let function_name = format!("{scope}_insert");
+    if sql_metadata
+        .function_overloads
+        .contains_key(&FunctionName(function_name.clone()))
+    {
+        cfg.service(
+            resource(format!("/{scope}_import"))
+                .route(route_import_form(scope, &function_name))
+                .route(route_import(scope, &function_name)),
+        );
+    }
+}
+
+/// CRUD - read (list) with order_by and pagination, without custom filters
+fn route_multi_row(scope: &str, view_name: &str) -> actix_web::Route {
+    let scope = scope.to_string();
+    let view_name = view_name.to_string();
+    to(
+        move |app_state: DataAppState, req: WebRequest, query: WebQuery, form: Option<WebForm>| {
+            let scope = scope.clone();
+            let view_name = view_name.clone();
+            async move {
+                let mut ssmr =
+                    ServerSideMultiRow::new(&app_state, &req, &scope, &view_name, &query, &form)?;
+                ssmr.run_multi_row_sql_and_process_html().await
+            }
+        },
+    )
+}
+
+/// export of the list as csv, tsv or excel with order_by, without pagination
+fn route_export(scope: &str, view_name: &str) -> actix_web::Route {
+    let scope = scope.to_string();
+    let view_name = view_name.to_string();
+    to(
+        move |app_state: DataAppState, req: WebRequest, query: WebQuery, form: Option<WebForm>| {
+            let scope = scope.clone();
+            let view_name = view_name.clone();
+            async move {
+                let mut ssmr =
+                    ServerSideMultiRow::new(&app_state, &req, &scope, &view_name, &query, &form)?;
+                ssmr.run_multi_row_sql_and_export().await
+            }
+        },
+    )
+}
+
+/// UI new, edit and CRUD create, read, update, delete for a single row
+fn route_single_row(scope: &str, function_name: &str) -> actix_web::Route {
+    let scope = scope.to_string();
+    let function_name = function_name.to_string();
+    to(
+        move |app_state: DataAppState, req: WebRequest, query: WebQuery, form: Option<WebForm>| {
+            let scope = scope.clone();
+            let function_name = function_name.clone();
+            async move {
+                let mut sssr = ServerSideSingleRow::new(
+                    &app_state,
+                    &req,
+                    &scope,
+                    &function_name,
+                    &query,
+                    &form,
+                )?;
+                sssr.run_single_row_sql_and_process_html().await
+            }
+        },
+    )
+}
+
+/// UI upload form for the csv import
+fn route_import_form(scope: &str, function_name: &str) -> actix_web::Route {
+    let scope = scope.to_string();
+    let function_name = function_name.to_string();
+    get().to(move |app_state: DataAppState, req: WebRequest| {
+        let scope = scope.clone();
+        let function_name = function_name.clone();
+        async move {
+            let ssi = ServerSideImport::new(&app_state, &req, &scope, &function_name)?;
+            ssi.run_import_form()
+        }
+    })
+}
+
+/// CRUD create many rows from the uploaded csv file
+fn route_import(scope: &str, function_name: &str) -> actix_web::Route {
+    let scope = scope.to_string();
+    let function_name = function_name.to_string();
+    post().to(
+        move |app_state: DataAppState, req: WebRequest, payload: actix_multipart::Multipart| {
+            let scope = scope.clone();
+            let function_name = function_name.clone();
+            async move {
+                let ssi = ServerSideImport::new(&app_state, &req, &scope, &function_name)?;
+                ssi.run_import_and_process_html(payload).await
+            }
+        },
+    )
+}
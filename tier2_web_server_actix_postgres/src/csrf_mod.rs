@@ -0,0 +1,115 @@
+//! csrf_mod.rs
+
+// Protection against cross-site request forgery with the double submit cookie.
+// Every browser session gets a random token in the cookie csrf_token.
+// The html templates put the same token in a hidden field with the placeholder {csrf_token}.
+// The data modifying requests must be POST and must submit the token
+// in the web param csrf_token or in the header X-CSRF-Token (for scripts).
+// Another site can send the cookie, but it cannot read it and submit it.
+
+use actix_web::dev::{Service, ServiceRequest, ServiceResponse};
+use actix_web::HttpMessage;
+
+use crate::error_mod::LibError;
+
+/// the same name is used for the cookie and the web param
+pub const CSRF_TOKEN_NAME: &str = "csrf_token";
+/// scripts can send the token in the header instead of the web param
+const CSRF_TOKEN_HEADER: &str = "x-csrf-token";
+
+/// the new token, if the request has no cookie yet
+struct NewCsrfToken(String);
+
+/// middleware for App::wrap_fn()
+/// If the request has no csrf cookie, a new token is generated
+/// and the cookie is set in the response.
+pub fn csrf_cookie_wrap<S, B>(
+    req: ServiceRequest,
+    srv: &S,
+) -> impl std::future::Future<Output = Result<ServiceResponse<B>, actix_web::Error>>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error>,
+{
+    let new_token = match req.cookie(CSRF_TOKEN_NAME) {
+        Some(_) => None,
+        None => Some(random_token()),
+    };
+    if let Some(token) = &new_token {
+        req.extensions_mut().insert(NewCsrfToken(token.clone()));
+    }
+    let future = srv.call(req);
+    async move {
+        let mut res = future.await?;
+        if let Some(token) = new_token {
+            let cookie = actix_web::cookie::Cookie::build(CSRF_TOKEN_NAME, token)
+                .path("/")
+                .http_only(true)
+                .same_site(actix_web::cookie::SameSite::Strict)
+                .finish();
+            res.response_mut().add_cookie(&cookie)?;
+        }
+        Ok(res)
+    }
+}
+
+/// random 32 bytes as hex string for tokens and session ids
+pub fn random_token() -> String {
+    use rand::Rng;
+    let bytes: [u8; 32] = rand::thread_rng().gen();
+    bytes.iter().map(|x| format!("{:02x}", x)).collect()
+}
+
+/// the token of this browser session for the placeholder {csrf_token}
+pub fn csrf_token(req: &actix_web::HttpRequest) -> String {
+    match req.extensions().get::<NewCsrfToken>() {
+        Some(new_token) => new_token.0.clone(),
+        None => req
+            .cookie(CSRF_TOKEN_NAME)
+            .map(|x| x.value().to_string())
+            .unwrap_or_default(),
+    }
+}
+
+/// The data modifying request must be POST with the same token as in the cookie.
+/// The token is submitted in the web param or in the header.
+#[track_caller]
+pub fn verify_csrf_token(
+    req: &actix_web::HttpRequest,
+    web_param_token: Option<&str>,
+) -> Result<(), LibError> {
+    let source_line_column = crate::error_mod::file_line_column(std::panic::Location::caller());
+    if req.method() != actix_web::http::Method::POST {
+        return Err(LibError::MethodNotAllowed {
+            user_friendly: format!("{} must use POST", req.path()),
+            developer_friendly: req.method().to_string(),
+            source_line_column,
+        });
+    }
+    let submitted_token = web_param_token.or_else(|| {
+        req.headers()
+            .get(CSRF_TOKEN_HEADER)
+            .and_then(|x| x.to_str().ok())
+    });
+    let cookie_token = req.cookie(CSRF_TOKEN_NAME);
+    match (submitted_token, cookie_token) {
+        (Some(submitted_token), Some(cookie_token))
+            if !submitted_token.is_empty()
+                && constant_time_eq(
+                    submitted_token.as_bytes(),
+                    cookie_token.value().as_bytes(),
+                ) =>
+        {
+            Ok(())
+        }
+        _ => Err(LibError::CsrfTokenInvalid {
+            user_friendly: req.path().to_string(),
+            developer_friendly: format!("submitted: {:?}", submitted_token),
+            source_line_column,
+        }),
+    }
+}
+
+/// the time of the comparison does not reveal how many bytes are equal
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
@@ -0,0 +1,220 @@
+//! csv_mod.rs
+
+// Export of list views as text files for spreadsheets and scripts.
+// The first line is the header with column names.
+// NULL is exported as an empty value.
+
+use crate::error_mod::LibError;
+use crate::web_params_mod::WebParams;
+
+/// export format from the web param: format=csv, format=tsv or format=excel
+/// excel is csv with the UTF-8 BOM, so Excel recognizes the encoding
+#[derive(strum::EnumString, strum::Display, Clone, Copy)]
+#[strum(serialize_all = "snake_case")]
+pub enum ExportFormat {
+    Csv,
+    Tsv,
+    Excel,
+}
+
+impl ExportFormat {
+    /// csv is the default if the web param format is missing or empty
+    #[track_caller]
+    pub fn from_web_params(web_params: &WebParams) -> Result<ExportFormat, LibError> {
+        use std::str::FromStr;
+        match web_params.0.get("format").map(|x| x.as_str()) {
+            None | Some("") => Ok(ExportFormat::Csv),
+            Some(format) => ExportFormat::from_str(format)
+                .map_err(|_| web_params.invalid_web_param("format", "csv, tsv or excel")),
+        }
+    }
+
+    /// content type for the http response
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv | ExportFormat::Excel => "text/csv; charset=utf-8",
+            ExportFormat::Tsv => "text/tab-separated-values; charset=utf-8",
+        }
+    }
+
+    /// extension for the file name
+    pub fn file_extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv | ExportFormat::Excel => "csv",
+            ExportFormat::Tsv => "tsv",
+        }
+    }
+
+    /// the first line of the file with the column names
+    pub fn header_line(&self, column_names: &[Option<String>]) -> String {
+        match self {
+            ExportFormat::Excel => format!("\u{feff}{}", self.line(column_names)),
+            _ => self.line(column_names),
+        }
+    }
+
+    /// one line with delimited and quoted values
+    pub fn line(&self, values: &[Option<String>]) -> String {
+        let values: Vec<String> = values
+            .iter()
+            .map(|x| x.as_deref().unwrap_or_default())
+            .map(|x| match self {
+                ExportFormat::Csv => csv_value(x),
+                ExportFormat::Tsv => tsv_value(x),
+                ExportFormat::Excel => csv_value(&excel_value(x)),
+            })
+            .collect();
+        match self {
+            ExportFormat::Csv | ExportFormat::Excel => format!("{}\r\n", values.join(",")),
+            ExportFormat::Tsv => format!("{}\n", values.join("\t")),
+        }
+    }
+}
+
+/// RFC 4180: values with comma, quote or newline are inside quotes, the quote is doubled
+fn csv_value(value: &str) -> String {
+    if value.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// tsv has no quoting: the special characters are escaped like in the postgres text format
+fn tsv_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('\t', "\\t")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+}
+
+/// Excel executes values that start with =, +, -, @, tab or carriage return as formulas.
+/// The apostrophe forces Excel to show them as text.
+/// Numbers like -5 or +3 are not formulas and stay numbers.
+fn excel_value(value: &str) -> String {
+    if value.starts_with(['=', '+', '-', '@', '\t', '\r']) && value.parse::<f64>().is_err() {
+        format!("'{value}")
+    } else {
+        value.to_string()
+    }
+}
+
+/// parse the csv text (RFC 4180) into records of values
+/// The BOM is ignored. Lines end with \r\n or \n. Empty lines are ignored.
+/// Returns the error message if a quoted value is not closed.
+pub fn parse_csv(text: &str) -> Result<Vec<Vec<String>>, String> {
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+    let mut records = vec![];
+    let mut record = vec![];
+    let mut value = String::new();
+    let mut in_quotes = false;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if in_quotes {
+            match c {
+                // the doubled quote is one quote inside the value
+                '"' if chars.peek() == Some(&'"') => {
+                    value.push('"');
+                    chars.next();
+                }
+                '"' => in_quotes = false,
+                _ => value.push(c),
+            }
+        } else {
+            match c {
+                '"' => in_quotes = true,
+                ',' => record.push(std::mem::take(&mut value)),
+                '\r' if chars.peek() == Some(&'\n') => (),
+                '\n' => {
+                    record.push(std::mem::take(&mut value));
+                    push_record(&mut records, std::mem::take(&mut record));
+                }
+                _ => value.push(c),
+            }
+        }
+    }
+    if in_quotes {
+        return Err(format!(
+            "the quoted value is not closed in record {}",
+            records.len() + 1
+        ));
+    }
+    record.push(value);
+    push_record(&mut records, record);
+    Ok(records)
+}
+
+/// the record with only one empty value is an empty line
+fn push_record(records: &mut Vec<Vec<String>>, record: Vec<String>) {
+    if !(record.len() == 1 && record[0].is_empty()) {
+        records.push(record);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn csv_value_quotes_special_characters() {
+        assert_eq!(csv_value("plain"), "plain");
+        assert_eq!(csv_value("a,b"), "\"a,b\"");
+        assert_eq!(csv_value("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_value("line\r\nbreak"), "\"line\r\nbreak\"");
+    }
+
+    #[test]
+    fn excel_value_keeps_numbers() {
+        assert_eq!(excel_value("-5"), "-5");
+        assert_eq!(excel_value("+3"), "+3");
+        assert_eq!(excel_value("-1.5e3"), "-1.5e3");
+        assert_eq!(excel_value("text"), "text");
+    }
+
+    #[test]
+    fn excel_value_escapes_formulas() {
+        assert_eq!(excel_value("=1+1"), "'=1+1");
+        assert_eq!(excel_value("@SUM(A1)"), "'@SUM(A1)");
+        assert_eq!(excel_value("+cmd|' /C calc'!A0"), "'+cmd|' /C calc'!A0");
+        assert_eq!(excel_value("-2+3"), "'-2+3");
+        assert_eq!(excel_value("\t=1"), "'\t=1");
+        assert_eq!(excel_value("\r=1"), "'\r=1");
+    }
+
+    #[test]
+    fn parse_csv_doubled_quotes() {
+        let records = parse_csv("a,\"say \"\"hi\"\"\"\r\n").unwrap();
+        assert_eq!(records, vec![vec!["a", "say \"hi\""]]);
+    }
+
+    #[test]
+    fn parse_csv_unclosed_quote() {
+        let err = parse_csv("id,webpage\r\n1,\"page 1\r\n").unwrap_err();
+        assert_eq!(err, "the quoted value is not closed in record 2");
+    }
+
+    #[test]
+    fn parse_csv_crlf_inside_quotes() {
+        let records = parse_csv("1,\"line\r\nbreak\"\r\n2,next\r\n").unwrap();
+        assert_eq!(records, vec![vec!["1", "line\r\nbreak"], vec!["2", "next"]]);
+    }
+
+    #[test]
+    fn parse_csv_ignores_bom() {
+        let records = parse_csv("\u{feff}id,webpage\n1,page 1").unwrap();
+        assert_eq!(records, vec![vec!["id", "webpage"], vec!["1", "page 1"]]);
+    }
+
+    #[test]
+    fn parse_csv_ignores_empty_lines() {
+        let records = parse_csv("id\r\n\r\n1\n\n2\r\n").unwrap();
+        assert_eq!(records, vec![vec!["id"], vec!["1"], vec!["2"]]);
+    }
+
+    #[test]
+    fn parse_csv_keeps_empty_values() {
+        let records = parse_csv("a,,c\r\n,\r\n").unwrap();
+        assert_eq!(records, vec![vec!["a", "", "c"], vec!["", ""]]);
+    }
+}
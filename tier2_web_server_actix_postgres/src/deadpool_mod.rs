@@ -1,32 +1,68 @@
 //! deadpool_mod.rs
 
+use crate::config_mod::{PgConfig, SslRootCert};
 use crate::error_mod::LibError;
 
 /// create and start the connection pool
-pub async fn deadpool_postgres_start() -> deadpool_postgres::Pool {
-    // this loads our .env file and includes the values in std::env
-    println!("Reading dotenv");
-    dotenv::dotenv().ok();
-    let mut pg_config = tokio_postgres::Config::new();
-    pg_config.host(std::env::var("PG.HOST").unwrap().as_str());
-    pg_config.user(std::env::var("PG.USER").unwrap().as_str());
-    pg_config.dbname(std::env::var("PG.DBNAME").unwrap().as_str());
+pub async fn deadpool_postgres_start(config: &PgConfig) -> deadpool_postgres::Pool {
+    let pg_config = config.connection.clone();
     let mgr_config = deadpool_postgres::ManagerConfig {
         recycling_method: deadpool_postgres::RecyclingMethod::Fast,
     };
-    let mgr = deadpool_postgres::Manager::from_config(pg_config, tokio_postgres::NoTls, mgr_config);
+    let mgr = match pg_config.get_ssl_mode() {
+        tokio_postgres::config::SslMode::Disable => {
+            deadpool_postgres::Manager::from_config(pg_config, tokio_postgres::NoTls, mgr_config)
+        }
+        _ => {
+            let tls = rustls_connector(config.ssl_root_cert.as_ref());
+            deadpool_postgres::Manager::from_config(pg_config, tls, mgr_config)
+        }
+    };
     println!("Create pool");
     let pool = deadpool_postgres::Pool::builder(mgr)
-        .max_size(16)
+        .max_size(config.pool_max_size)
+        .wait_timeout(config.pool_wait_timeout)
+        .create_timeout(config.pool_create_timeout)
+        .recycle_timeout(config.pool_recycle_timeout)
+        // the timeouts need the runtime
+        .runtime(deadpool_postgres::Runtime::Tokio1)
         .build()
         .unwrap();
     // return
     pool
 }
 
+/// TLS with rustls for sslmode prefer and require
+/// The server certificate is always verified with the CA certificates from the pem file
+/// or with the webpki root certificates.
+/// The pem file is already validated when the config is read.
+pub fn rustls_connector(
+    ssl_root_cert: Option<&SslRootCert>,
+) -> tokio_postgres_rustls::MakeRustlsConnect {
+    let root_store = match ssl_root_cert {
+        Some(ssl_root_cert) => ssl_root_cert.root_store.clone(),
+        None => {
+            let mut root_store = rustls::RootCertStore::empty();
+            root_store.add_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.iter().map(|ta| {
+                rustls::OwnedTrustAnchor::from_subject_spki_name_constraints(
+                    ta.subject,
+                    ta.spki,
+                    ta.name_constraints,
+                )
+            }));
+            root_store
+        }
+    };
+    let tls_config = rustls::ClientConfig::builder()
+        .with_safe_defaults()
+        .with_root_certificates(root_store)
+        .with_no_client_auth();
+    tokio_postgres_rustls::MakeRustlsConnect::new(tls_config)
+}
+
 /// start and check the connection pool to postgres
-pub async fn deadpool_start_and_check() -> deadpool_postgres::Pool {
-    let pool = crate::deadpool_mod::deadpool_postgres_start().await;
+pub async fn deadpool_start_and_check(config: &PgConfig) -> deadpool_postgres::Pool {
+    let pool = crate::deadpool_mod::deadpool_postgres_start(config).await;
     // Check the connection to postgres database and panic if error
     let _postgres_client: deadpool_postgres::Client = pool.get().await.unwrap();
     pool
@@ -41,3 +77,19 @@ pub async fn get_postgres_client_from_pool(
         .await
         .map_err(|_| crate::error_mod::LibError::DatabaseConnection)
 }
+
+/// roll back the open transaction in drop(), where await is not possible
+pub fn spawn_rollback(postgres_client: deadpool_postgres::Object) {
+    actix_web::rt::spawn(async move {
+        if let Err(err) = postgres_client.batch_execute("ROLLBACK").await {
+            log::error!("Rollback on drop failed: {err}");
+            discard_client(postgres_client);
+        }
+    });
+}
+
+/// the client with a failed ROLLBACK can still have the transaction open
+/// It is taken out of the pool and the connection is closed, so it is never recycled.
+pub fn discard_client(postgres_client: deadpool_postgres::Object) {
+    drop(deadpool_postgres::Object::take(postgres_client));
+}
@@ -8,6 +8,13 @@ pub enum LibError {
     /// Database connection error.
     #[error("Database connection error.")]
     DatabaseConnection,
+    /// Configuration error: {user_friendly}
+    #[error("Configuration error:\n{user_friendly}")]
+    Config {
+        user_friendly: String,
+        developer_friendly: String,
+        source_line_column: String,
+    },
     /// Query error: {user_friendly}
     #[error("Query error: {user_friendly}")]
     QueryError {
@@ -30,18 +37,143 @@ pub enum LibError {
         developer_friendly: String,
         source_line_column: String,
     },
+    /// The value in web query is not valid: {user_friendly}
+    #[error("The value in web query is not valid: {user_friendly}")]
+    InvalidWebParam {
+        user_friendly: String,
+        developer_friendly: String,
+        source_line_column: String,
+    },
+    /// The sql object does not exist: {user_friendly}
+    #[error("The sql object does not exist: {user_friendly}")]
+    SqlMetadataNotFound {
+        user_friendly: String,
+        developer_friendly: String,
+        source_line_column: String,
+    },
+    /// The html template does not exist: {user_friendly}
+    #[error("The html template does not exist: {user_friendly}")]
+    TemplateNotFound {
+        user_friendly: String,
+        developer_friendly: String,
+        source_line_column: String,
+    },
+    /// The html template has no row fragment: {user_friendly}
+    #[error("The html template has no row fragment: {user_friendly}")]
+    TemplateRowFragmentNotFound {
+        user_friendly: String,
+        developer_friendly: String,
+        source_line_column: String,
+    },
+    /// The postgres data type is not supported: {user_friendly}
+    #[error("The postgres data type is not supported: {user_friendly}")]
+    UnsupportedPostgresType {
+        user_friendly: String,
+        developer_friendly: String,
+        source_line_column: String,
+    },
+    /// Forbidden: {user_friendly}
+    #[error("Forbidden: {user_friendly}")]
+    Forbidden {
+        user_friendly: String,
+        developer_friendly: String,
+        source_line_column: String,
+    },
+    /// Unauthorized: {user_friendly}
+    #[error("Unauthorized: {user_friendly}")]
+    Unauthorized {
+        user_friendly: String,
+        developer_friendly: String,
+        source_line_column: String,
+    },
+    /// The request method is not allowed: {user_friendly}
+    #[error("The request method is not allowed: {user_friendly}")]
+    MethodNotAllowed {
+        user_friendly: String,
+        developer_friendly: String,
+        source_line_column: String,
+    },
+    /// The csrf token is missing or not valid: {user_friendly}
+    #[error(
+        "The csrf token is missing or not valid. Reload the page and try again: {user_friendly}"
+    )]
+    CsrfTokenInvalid {
+        user_friendly: String,
+        developer_friendly: String,
+        source_line_column: String,
+    },
+    /// Transaction error: {user_friendly}
+    #[error("Transaction error: {user_friendly}")]
+    Transaction {
+        user_friendly: String,
+        developer_friendly: String,
+        source_line_column: String,
+    },
+    /// The blocking task failed: {user_friendly}
+    #[error("The blocking task failed: {user_friendly}")]
+    BlockingTask {
+        user_friendly: String,
+        developer_friendly: String,
+        source_line_column: String,
+    },
+    /// The record does not exist: {user_friendly}
+    #[error("The record does not exist: {user_friendly}")]
+    RowNotFound {
+        user_friendly: String,
+        developer_friendly: String,
+        source_line_column: String,
+    },
     /*
         #[error(transparent)]
         Unknown(#[from] anyhow::Error),
     */
 }
 
+/// the template cache and the key of the error page are set once on start
+/// The template is read from the cache on every error, so hot_reload works also for the error page.
+static ERROR_TEMPLATE: std::sync::OnceLock<(
+    std::sync::Arc<crate::html_templating_mod::HtmlTemplateCache>,
+    String,
+)> = std::sync::OnceLock::new();
+
+/// set the html template for the error page, so the user stays inside the web app UI
+/// placeholders: {status_code}, {status_reason}, {time_epoch}, {error_message}
+pub fn set_error_template(
+    html_templates: std::sync::Arc<crate::html_templating_mod::HtmlTemplateCache>,
+    template_key: String,
+) {
+    // the template can be set only once
+    let _ = ERROR_TEMPLATE.set((html_templates, template_key));
+}
+
+/// the error page template from the cache, None if it is not set or missing
+fn error_template() -> Option<std::sync::Arc<crate::html_templating_mod::HtmlTemplate>> {
+    let (html_templates, template_key) = ERROR_TEMPLATE.get()?;
+    html_templates.get_by_key(template_key).ok()
+}
+
 /// actix error has this trait for custom errors
 impl actix_web::ResponseError for LibError {
     /// html status code for error
     fn status_code(&self) -> actix_web::http::StatusCode {
-        match *self {
-            _ => actix_web::http::StatusCode::INTERNAL_SERVER_ERROR,
+        use actix_web::http::StatusCode;
+        match self {
+            LibError::GetStrFromWebParams { .. }
+            | LibError::GetI32FromWebParams { .. }
+            | LibError::InvalidWebParam { .. } => StatusCode::BAD_REQUEST,
+            LibError::QueryError { source_error, .. }
+                if source_error.code()
+                    == Some(&tokio_postgres::error::SqlState::UNIQUE_VIOLATION) =>
+            {
+                StatusCode::CONFLICT
+            }
+            LibError::RowNotFound { .. } => StatusCode::NOT_FOUND,
+            LibError::Unauthorized { .. } => StatusCode::UNAUTHORIZED,
+            LibError::Forbidden { .. } => StatusCode::FORBIDDEN,
+            LibError::MethodNotAllowed { .. } => StatusCode::METHOD_NOT_ALLOWED,
+            LibError::CsrfTokenInvalid { .. } => StatusCode::FORBIDDEN,
+            LibError::DatabaseConnection => StatusCode::SERVICE_UNAVAILABLE,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
         }
     }
     /// Log the developer_friendly on the server
@@ -54,17 +186,68 @@ impl actix_web::ResponseError for LibError {
         // log is developer friendly with many more info
         log::error!("{time} {}\n{:#?}", self, self);
         // only the user-friendly error for the user
-        actix_web::HttpResponse::build(status_code).body(format!("{time} {}", self))
+        match error_template() {
+            Some(template) => {
+                let body = template
+                    .text
+                    .replace("{status_code}", status_code.as_str())
+                    .replace(
+                        "{status_reason}",
+                        status_code.canonical_reason().unwrap_or_default(),
+                    )
+                    .replace("{time_epoch}", &time.to_string())
+                    .replace(
+                        "{error_message}",
+                        &html_escape::encode_text(&self.to_string()),
+                    );
+                actix_web::HttpResponse::build(status_code)
+                    .content_type(mime::TEXT_HTML_UTF_8)
+                    .body(body)
+            }
+            None => actix_web::HttpResponse::build(status_code).body(format!("{time} {}", self)),
+        }
+    }
+}
+
+/// LibError for the json api clients
+/// the same status code, but the body is json instead of html
+#[derive(Debug)]
+pub struct JsonLibError(pub LibError);
+
+impl std::fmt::Display for JsonLibError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl actix_web::ResponseError for JsonLibError {
+    fn status_code(&self) -> actix_web::http::StatusCode {
+        self.0.status_code()
+    }
+    /// Log the developer_friendly on the server
+    /// respond the user_friendly to the user as json
+    fn error_response(&self) -> actix_web::HttpResponse {
+        let status_code = self.status_code();
+        let time = time_epoch();
+        log::error!("{time} {}\n{:#?}", self.0, self.0);
+        let body = serde_json::json!({
+            "status_code": status_code.as_u16(),
+            "status_reason": status_code.canonical_reason().unwrap_or_default(),
+            "time_epoch": time.to_string(),
+            "error_message": self.0.to_string(),
+        });
+        actix_web::HttpResponse::build(status_code)
+            .content_type(mime::APPLICATION_JSON)
+            .body(body.to_string())
     }
 }
 
 /// time as a big Unix epoch int
 pub fn time_epoch() -> u128 {
-    let time = std::time::SystemTime::now()
+    std::time::SystemTime::now()
         .duration_since(std::time::UNIX_EPOCH)
         .unwrap()
-        .as_millis();
-    time
+        .as_millis()
 }
 
 pub fn file_line_column(source_caller_location: &std::panic::Location) -> String {
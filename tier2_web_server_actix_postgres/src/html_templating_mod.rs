@@ -1,38 +1,177 @@
 // html_templating_mod.rs
 
-/// struct fields scope and function name are used as the folder and file name for the template
-pub fn read_template(scope: &str, name: &str) -> String {
-    let path = format!("webpage_hits_admin/{}/{}.html", scope, name);
-    std::fs::read_to_string(path).unwrap()
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::SystemTime;
+
+use crate::error_mod::LibError;
+
+/// html template read from disk
+/// the fragment between <!--row_start--> and <!--row_end--> is located only once
+pub struct HtmlTemplate {
+    pub text: String,
+    pub row_fragment: Option<RowFragment>,
+    modified: Option<SystemTime>,
+}
+
+/// positions of the fragment that is repeated for every row
+/// outer includes the markers, inner is only the content between them
+pub struct RowFragment {
+    pub start_outer: usize,
+    pub start_inner: usize,
+    pub end_inner: usize,
+    pub end_outer: usize,
+}
+
+/// all html templates are read and parsed once on start and stored in AppState
+/// In hot_reload mode the modified time of the file is checked on every use,
+/// so the edited templates are visible without restarting the server (for development).
+pub struct HtmlTemplateCache {
+    template_root: PathBuf,
+    hot_reload: bool,
+    templates: RwLock<HashMap<String, Arc<HtmlTemplate>>>,
+}
+
+impl HtmlTemplate {
+    /// read the template from disk and locate the row fragment
+    fn read(path: &Path) -> Result<HtmlTemplate, LibError> {
+        let text = std::fs::read_to_string(path).map_err(|err| LibError::TemplateNotFound {
+            user_friendly: path.to_string_lossy().to_string(),
+            developer_friendly: format!("{err}"),
+            source_line_column: format!("{}:{}:{}", file!(), line!(), column!()),
+        })?;
+        let modified = std::fs::metadata(path).and_then(|x| x.modified()).ok();
+        let row_fragment = match (text.find("<!--row_start-->"), text.find("<!--row_end-->")) {
+            (Some(start_outer), Some(end_inner)) if start_outer < end_inner => Some(RowFragment {
+                start_outer,
+                start_inner: start_outer + "<!--row_start-->".len(),
+                end_inner,
+                end_outer: end_inner + "<!--row_end-->".len(),
+            }),
+            _ => None,
+        };
+        Ok(HtmlTemplate {
+            text,
+            row_fragment,
+            modified,
+        })
+    }
+
+    /// the fragment from <!--row_start--> to <!--row_end--> that will be repeated for every Row
+    pub fn row_fragment(&self, template_name: &str) -> Result<&RowFragment, LibError> {
+        self.row_fragment
+            .as_ref()
+            .ok_or_else(|| LibError::TemplateRowFragmentNotFound {
+                user_friendly: template_name.to_string(),
+                developer_friendly: "<!--row_start--> or <!--row_end--> is missing".to_string(),
+                source_line_column: format!("{}:{}:{}", file!(), line!(), column!()),
+            })
+    }
+}
+
+impl HtmlTemplateCache {
+    /// read all the *.html files in the template_root folder and subfolders
+    pub fn load_all(template_root: &str, hot_reload: bool) -> Result<HtmlTemplateCache, LibError> {
+        let template_cache = HtmlTemplateCache {
+            template_root: PathBuf::from(template_root),
+            hot_reload,
+            templates: RwLock::new(HashMap::new()),
+        };
+        let mut templates = HashMap::new();
+        template_cache.load_folder(&template_cache.template_root, &mut templates)?;
+        println!("Html templates loaded: {}", templates.len());
+        *template_cache.templates.write().unwrap() = templates;
+        Ok(template_cache)
+    }
+
+    /// recursive read of the folder
+    fn load_folder(
+        &self,
+        folder: &Path,
+        templates: &mut HashMap<String, Arc<HtmlTemplate>>,
+    ) -> Result<(), LibError> {
+        let read_dir = std::fs::read_dir(folder).map_err(|err| LibError::TemplateNotFound {
+            user_friendly: folder.to_string_lossy().to_string(),
+            developer_friendly: format!("{err}"),
+            source_line_column: format!("{}:{}:{}", file!(), line!(), column!()),
+        })?;
+        for entry in read_dir.flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                self.load_folder(&path, templates)?;
+            } else if path.extension().is_some_and(|x| x == "html") {
+                let key = path
+                    .strip_prefix(&self.template_root)
+                    .unwrap_or(&path)
+                    .with_extension("")
+                    .to_string_lossy()
+                    .to_string();
+                templates.insert(key, Arc::new(HtmlTemplate::read(&path)?));
+            }
+        }
+        Ok(())
+    }
+
+    /// struct fields scope and function name are used as the folder and file name for the template
+    pub fn get(&self, scope: &str, name: &str) -> Result<Arc<HtmlTemplate>, LibError> {
+        self.get_by_key(&format!("{}/{}", scope, name))
+    }
+
+    /// the key is the path relative to template_root without the extension: "scope/name"
+    pub fn get_by_key(&self, key: &str) -> Result<Arc<HtmlTemplate>, LibError> {
+        let key = key.to_string();
+        let cached = self.templates.read().unwrap().get(&key).cloned();
+        match cached {
+            Some(template) if !self.hot_reload => Ok(template),
+            Some(template) if template.modified == self.modified_on_disk(&key) => Ok(template),
+            None if !self.hot_reload => Err(LibError::TemplateNotFound {
+                user_friendly: key,
+                developer_friendly: format!("not in cache: {:?}", self.template_root),
+                source_line_column: format!("{}:{}:{}", file!(), line!(), column!()),
+            }),
+            // in hot_reload mode read the new or modified file from disk
+            _ => {
+                let template = Arc::new(HtmlTemplate::read(&self.path(&key))?);
+                println!("Html template reloaded: {key}");
+                self.templates
+                    .write()
+                    .unwrap()
+                    .insert(key, template.clone());
+                Ok(template)
+            }
+        }
+    }
+
+    /// path on disk for the template key
+    fn path(&self, key: &str) -> PathBuf {
+        self.template_root.join(format!("{key}.html"))
+    }
+
+    /// modified time of the file on disk
+    fn modified_on_disk(&self, key: &str) -> Option<SystemTime> {
+        std::fs::metadata(self.path(key))
+            .and_then(|x| x.modified())
+            .ok()
+    }
 }
 
 /// simple replace placeholders with values from Row
 /// inside the template there are delimited variables like: {variable}
 /// the data comes from the postgres database inside a Row struct
-/// panics if the type is not recognized
+/// NULL is replaced with the null_marker
 pub fn template_replace_fields_from_single_row(
     text_with_placeholders: &str,
     single_row: tokio_postgres::Row,
-) -> String {
+    null_marker: &str,
+) -> Result<String, LibError> {
     let mut text_replaced = text_with_placeholders.to_string();
     for (i, col) in single_row.columns().iter().enumerate() {
         let placeholder = String::new() + "{" + col.name() + "}";
-        // postgres has a very long list of types
-        // I will use only a small subset of them
-        use tokio_postgres::types::Type;
-        match col.type_() {
-            &Type::TEXT | &Type::VARCHAR => {
-                let value: String = single_row.get(i);
-                let value = html_escape::encode_text(&value);
-                text_replaced = text_replaced.replace(&placeholder, &value)
-            }
-            &Type::INT4 => {
-                let value: i32 = single_row.get(i);
-                text_replaced = text_replaced.replace(&placeholder, &value.to_string())
-            }
-            &Type::VOID => (),
-            _ => panic!("Unrecognized postgres type: {:?}", col.type_()),
-        }
+        let value = crate::postgres_type_mod::row_value_to_string(&single_row, i)?;
+        let value = value.as_deref().unwrap_or(null_marker);
+        let value = html_escape::encode_double_quoted_attribute(value);
+        text_replaced = text_replaced.replace(&placeholder, &value);
     }
-    text_replaced
+    Ok(text_replaced)
 }
@@ -2,18 +2,34 @@
 
 mod actix_mod;
 mod app_state_mod;
+mod audit_log_mod;
+mod audit_mod;
+mod auth_mod;
+mod config_mod;
+mod crud_router_mod;
+mod csrf_mod;
+mod csv_mod;
 mod deadpool_mod;
 mod error_mod;
 mod html_templating_mod;
 mod postgres_mod;
 mod postgres_type_mod;
+mod server_side_import_mod;
 mod server_side_multi_row_mod;
 mod server_side_single_row_mod;
+mod sql_metadata_mod;
 mod web_params_mod;
 mod webpage_hits_mod;
 
 pub use actix_mod::config_route_main;
 pub use app_state_mod::AppState;
+pub use auth_mod::{hash_password, AuthenticatedUser};
+pub use config_mod::{AppConfig, Cli, CliCommand};
+pub use csrf_mod::csrf_cookie_wrap;
 pub use deadpool_mod::deadpool_start_and_check;
-pub use postgres_mod::get_for_cache_all_function_input_params;
-pub use postgres_mod::get_for_cache_all_view_fields;
+pub use error_mod::set_error_template;
+pub use error_mod::LibError;
+pub use html_templating_mod::HtmlTemplateCache;
+pub use server_side_multi_row_mod::ServerSideMultiRow;
+pub use server_side_single_row_mod::ServerSideSingleRow;
+pub use sql_metadata_mod::{spawn_sql_metadata_listener, SqlMetadata, SqlMetadataCache};
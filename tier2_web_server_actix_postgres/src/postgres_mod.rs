@@ -1,151 +1,305 @@
 //! postgres_mod.rs
 
 // type alias to make it more concise, precise and readable
-/// params are mostly searched by param name
-pub type ParamsNameType = HashMap<ParamName, PostgresInputType>;
-/// functions are always searched by function name
-pub type SqlFunctionInputParams = HashMap<FunctionName, ParamsNameType>;
+/// the overloaded functions have the same name, so they are searched by the FunctionKey
 /// functions params must be in correct order
-pub type SqlFunctionInputParamsOrder = HashMap<FunctionName, Vec<ParamName>>;
+pub type SqlFunctionInputParams = HashMap<FunctionKey, Vec<FunctionParam>>;
+/// the overloads are searched by the name with the schema and by the visible name without it
+pub type SqlFunctionOverloads = HashMap<FunctionName, Vec<FunctionKey>>;
 /// fields are always searched by field name
 pub type FieldsNameType = HashMap<FieldName, PostgresFieldType>;
 /// views are always searched by view name
 pub type SqlViewFields = HashMap<ViewName, FieldsNameType>;
+/// roles are searched by role name, the value is the set of allowed function and view names
+pub type SqlRolePermissions = HashMap<String, HashSet<String>>;
 
 // newtypes : forces unambiguous intent
 #[derive(Eq, Hash, PartialEq, Clone)]
 pub struct FunctionName(pub String);
 #[derive(Eq, Hash, PartialEq, Clone)]
 pub struct ParamName(pub String);
+
+/// the function in postgres is unique by schema, name and the types of the input params
+#[derive(Eq, Hash, PartialEq, Clone, Debug)]
+pub struct FunctionKey {
+    pub schema_name: String,
+    pub function_name: String,
+    /// the types of the input params: integer, character varying, integer
+    pub signature: String,
+}
+
+/// input param of the sql function
+#[derive(Clone)]
+pub struct FunctionParam {
+    pub param_name: ParamName,
+    pub input_type: PostgresInputType,
+    /// the param with the DEFAULT clause can be omitted in the call
+    pub has_default: bool,
+}
+
+/// one overload of the sql function with its input params
+/// For the call, only the params with values from the web params are in the vector.
+#[derive(Clone)]
+pub struct FunctionOverload {
+    pub function_key: FunctionKey,
+    pub params: Vec<FunctionParam>,
+}
+
+impl FunctionName {
+    /// functions with these suffixes modify data
+    /// they must be called with POST and the csrf token
+    pub fn is_data_modifying(&self) -> bool {
+        ["_insert", "_update", "_delete"]
+            .iter()
+            .any(|x| self.0.ends_with(x))
+    }
+}
+
+impl ParamName {
+    /// Postgres input variables can be prefixed with "in_" or just "_".
+    /// The web param has the name without the prefix.
+    pub fn web_param_name(&self) -> &str {
+        self.0.trim_start_matches('_').trim_start_matches("in_")
+    }
+}
+
+impl FunctionKey {
+    /// the schema-qualified name, so the search_path does not matter
+    pub fn qualified_name(&self) -> String {
+        format!(
+            "{}.{}",
+            quote_identifier(&self.schema_name),
+            quote_identifier(&self.function_name)
+        )
+    }
+}
+
+impl FunctionOverload {
+    /// the placeholders are cast to the types of the input params,
+    /// so postgres calls exactly this overload
+    /// The named notation allows to omit the params with defaults: fn("_id" => $1::integer)
+    pub fn select_query(&self) -> String {
+        let placeholders = self
+            .params
+            .iter()
+            .enumerate()
+            .map(|(i, param)| {
+                format!(
+                    "{} => ${}::{}",
+                    quote_identifier(&param.param_name.0),
+                    i + 1,
+                    param.input_type.as_ref()
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        format!(
+            "SELECT * from {}({});",
+            self.function_key.qualified_name(),
+            placeholders
+        )
+    }
+}
+
 #[derive(Eq, Hash, PartialEq)]
 pub struct ViewName(pub String);
-#[derive(Eq, Hash, PartialEq)]
+#[derive(Eq, Hash, PartialEq, Clone)]
 pub struct FieldName(pub String);
 
 use crate::{
     error_mod::LibError,
     postgres_type_mod::{PostgresFieldType, PostgresInputType},
 };
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use tokio_postgres::error::SqlState;
 
 /// run the query and catch the many different sql errors
-#[track_caller]
 pub async fn run_sql_select_query_pool(
     db_pool: &deadpool_postgres::Pool,
     query: &str,
     params: &[&(dyn tokio_postgres::types::ToSql + Sync)],
 ) -> Result<Vec<tokio_postgres::Row>, LibError> {
     let postgres_client = crate::deadpool_mod::get_postgres_client_from_pool(db_pool).await?;
-    postgres_client.query(query, params).await.map_err(|err| {
-        /*
-        many different sql errors:
-        https://github.com/sfackler/rust-postgres/blob/master/tokio-postgres/src/error/sqlstate.rs
-        code: SqlState( E42804, ), DATATYPE_MISMATCH
-            message: "structure of query does not match function result type",
-            detail: Some( "Returned type character varying(100) does not match expected type text in column 2.", ),
-            where_: Some( "PL/pgSQL function webpage_hits_insert(integer,text,integer) line 12 at RETURN QUERY", ),
-        code: SqlState( E23505, ), SqlState::UNIQUE_VIOLATION
-            message: "duplicate key value violates unique constraint \"webpage_uniq_webpage\"",
-            detail: Some( "Key (webpage)=(test) already exists.", ),
-            where_: Some( "SQL statement \"insert into webpage ( \"id\", webpage)\nvalues (_id, _webpage)\"\nPL/pgSQL function webpage_hits_insert(integer,text,integer) line 6 at SQL statement", ),
-        */
-        let err_code = err.code().unwrap().clone();
-        match err_code {
-            SqlState::UNIQUE_VIOLATION =>
-            // duplicate key value violates unique constraint
-            {
-                LibError::QueryError {
-                    user_friendly: format!("{}", err),
-                    source_error: err,
-                    developer_friendly: format!("{:?} {} {:?}", err_code, query, params),
-                    source_line_column: format!("{}:{}:{}", file!(), line!(), column!()),
-                }
-            }
-            SqlState::DATATYPE_MISMATCH =>
-            // structure of query does not match function result type
-            {
-                LibError::QueryError {
-                    user_friendly: format!("{}", err),
-                    source_error: err,
-                    developer_friendly: format!("{:?} {} {:?}", err_code, query, params),
-                    source_line_column: format!("{}:{}:{}", file!(), line!(), column!()),
-                }
+    postgres_client
+        .query(query, params)
+        .await
+        .map_err(|err| sql_error_to_lib_error(err, query, params))
+}
+
+/// the row-level security policies read the logged in user with
+/// nullif(current_setting('app.user_id', true), '')::integer
+/// The setting is local to the transaction, so it never stays on the pooled client.
+pub async fn set_local_app_user_id(
+    postgres_client: &impl tokio_postgres::GenericClient,
+    app_user_id: Option<i32>,
+) -> Result<(), LibError> {
+    let query = "SELECT set_config('app.user_id', $1, true);";
+    let app_user_id = app_user_id.map(|x| x.to_string()).unwrap_or_default();
+    postgres_client
+        .execute(query, &[&app_user_id])
+        .await
+        .map_err(|err| sql_error_to_lib_error(err, query, &[&app_user_id]))?;
+    Ok(())
+}
+
+/// convert the many different sql errors to LibError in a single place
+pub fn sql_error_to_lib_error(
+    err: tokio_postgres::Error,
+    query: &str,
+    params: &[&(dyn tokio_postgres::types::ToSql + Sync)],
+) -> LibError {
+    /*
+    many different sql errors:
+    https://github.com/sfackler/rust-postgres/blob/master/tokio-postgres/src/error/sqlstate.rs
+    code: SqlState( E42804, ), DATATYPE_MISMATCH
+        message: "structure of query does not match function result type",
+        detail: Some( "Returned type character varying(100) does not match expected type text in column 2.", ),
+        where_: Some( "PL/pgSQL function webpage_hits_insert(integer,text,integer) line 12 at RETURN QUERY", ),
+    code: SqlState( E23505, ), SqlState::UNIQUE_VIOLATION
+        message: "duplicate key value violates unique constraint \"webpage_uniq_webpage\"",
+        detail: Some( "Key (webpage)=(test) already exists.", ),
+        where_: Some( "SQL statement \"insert into webpage ( \"id\", webpage)\nvalues (_id, _webpage)\"\nPL/pgSQL function webpage_hits_insert(integer,text,integer) line 6 at SQL statement", ),
+    */
+    // errors without code are not from the database server
+    let err_code = err.code().cloned();
+    match err_code {
+        Some(SqlState::UNIQUE_VIOLATION) =>
+        // duplicate key value violates unique constraint
+        {
+            LibError::QueryError {
+                user_friendly: format!("{}", err),
+                source_error: err,
+                developer_friendly: format!("{:?} {} {:?}", err_code, query, params),
+                source_line_column: format!("{}:{}:{}", file!(), line!(), column!()),
             }
-            _ => LibError::QueryError {
+        }
+        Some(SqlState::DATATYPE_MISMATCH) =>
+        // structure of query does not match function result type
+        {
+            LibError::QueryError {
                 user_friendly: format!("{}", err),
                 source_error: err,
-                developer_friendly: format!("{} {:?}", query, params),
+                developer_friendly: format!("{:?} {} {:?}", err_code, query, params),
                 source_line_column: format!("{}:{}:{}", file!(), line!(), column!()),
-            },
+            }
         }
-    })
+        // the connection to the database is lost
+        None if err.is_closed() => LibError::DatabaseConnection,
+        _ => LibError::QueryError {
+            user_friendly: format!("{}", err),
+            source_error: err,
+            developer_friendly: format!("{} {:?}", query, params),
+            source_line_column: format!("{}:{}:{}", file!(), line!(), column!()),
+        },
+    }
 }
 
-/// from params deduce parameters for placeholders in sql queries
-pub fn prepare_placeholders_for_sql_params(
-    params: &[&(dyn tokio_postgres::types::ToSql + Sync)],
-) -> String {
-    let mut placeholders = String::new();
-    let mut delimiter = String::new();
-    // starts from 1
-    for i in 1..=params.len() {
-        placeholders.push_str(&format!("{delimiter}${i}"));
-        if delimiter.is_empty() {
-            delimiter.push_str(", ");
-        }
-    }
-    placeholders
+/// quote the name of a database object, so it can be safely used in the sql text
+pub fn quote_identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
 }
 
 /// Vector of all function input params with data types.
-/// Call it once on application start and store the result in a global variable.
+/// Call it on application start and store the result in the SqlMetadataCache.
 /// Postgres input variables can be prefixed with "in_" or just "_". Take it into consideration.
+/// Functions with unknown data types are logged and skipped. They cannot be called from the web app.
+/// Every function is in the overloads with the schema-qualified name.
+/// The functions visible in the search_path are also in the overloads with the name without schema.
+/// The view returns one row for every argument, so there is no parsing of the definition text.
 pub async fn get_for_cache_all_function_input_params(
     db_pool: &deadpool_postgres::Pool,
-) -> (SqlFunctionInputParams, SqlFunctionInputParamsOrder) {
-    let query = "SELECT proname, args_def from get_function_input_params;";
-    let vec_row = run_sql_select_query_pool(db_pool, query, &[])
-        .await
-        .unwrap();
+) -> Result<(SqlFunctionInputParams, SqlFunctionOverloads), LibError> {
+    let query = "SELECT nspname, proname, is_visible, signature, \
+    arg_name, arg_mode, arg_type_oid, has_default from get_function_input_params;";
+    let vec_row = run_sql_select_query_pool(db_pool, query, &[]).await?;
     let mut function_input_params: SqlFunctionInputParams = HashMap::new();
-    let mut function_input_params_order: SqlFunctionInputParamsOrder = HashMap::new();
+    let mut visible_functions = HashSet::new();
+    // the reason is logged only once for every function
+    let mut skipped_functions = HashMap::new();
     for row in vec_row.iter() {
-        // newtype
-        let function_name = FunctionName(row.get(0));
-        //dbg!(&function_name);
-        let args_def: String = row.get(1);
-        //dbg!(&args_def);
-        let mut hm_name_type: ParamsNameType = HashMap::new();
-        let mut params_order = vec![];
-        if !args_def.is_empty() {
-            for name_and_type in args_def.split(", ") {
-                let mut spl = name_and_type.split(' ');
-                let param_name = ParamName(spl.next().unwrap().to_string());
-                // ignore OUT parameters, only input parameters
-                if param_name.0 != "OUT" {
-                    let arg_type = spl.next().unwrap().to_string();
-                    use std::str::FromStr;
-                    let arg_type = PostgresInputType::from_str(&arg_type).unwrap();
-                    hm_name_type.insert(param_name.clone(), arg_type);
-                    params_order.push(param_name);
-                }
+        let function_key = FunctionKey {
+            schema_name: row.get(0),
+            function_name: row.get(1),
+            signature: row.get(3),
+        };
+        //dbg!(&function_key);
+        if row.get::<_, bool>(2) {
+            visible_functions.insert(function_key.clone());
+        }
+        let params = function_input_params
+            .entry(function_key.clone())
+            .or_default();
+        // the function without arguments has one row with NULL
+        let Some(arg_mode) = row.get::<_, Option<String>>(5) else {
+            continue;
+        };
+        // only the input params: IN, INOUT and VARIADIC
+        if !["i", "b", "v"].contains(&arg_mode.as_str()) {
+            continue;
+        }
+        let param_name = ParamName(row.get(4));
+        // the web param is found by the name of the input param
+        if param_name.0.is_empty() {
+            skipped_functions
+                .entry(function_key)
+                .or_insert_with(|| "the input param has no name".to_string());
+            continue;
+        }
+        let arg_type_oid: u32 = row.get(6);
+        match PostgresInputType::from_oid(arg_type_oid) {
+            Some(input_type) => params.push(FunctionParam {
+                param_name,
+                input_type,
+                has_default: row.get(7),
+            }),
+            None => {
+                skipped_functions.entry(function_key).or_insert_with(|| {
+                    format!(
+                        "unknown data type oid {arg_type_oid} in param: {}",
+                        param_name.0
+                    )
+                });
             }
         }
-        //dbg!(&vec_name_type);
-        function_input_params.insert(function_name.clone(), hm_name_type);
-        function_input_params_order.insert(function_name, params_order);
     }
-    (function_input_params, function_input_params_order)
+    for (function_key, reason) in skipped_functions {
+        log::warn!(
+            "Function {}.{}({}) skipped: {reason}",
+            function_key.schema_name,
+            function_key.function_name,
+            function_key.signature
+        );
+        function_input_params.remove(&function_key);
+    }
+
+    let mut function_overloads: SqlFunctionOverloads = HashMap::new();
+    for function_key in function_input_params.keys() {
+        let qualified_name = FunctionName(format!(
+            "{}.{}",
+            function_key.schema_name, function_key.function_name
+        ));
+        function_overloads
+            .entry(qualified_name)
+            .or_default()
+            .push(function_key.clone());
+        if visible_functions.contains(function_key) {
+            function_overloads
+                .entry(FunctionName(function_key.function_name.clone()))
+                .or_default()
+                .push(function_key.clone());
+        }
+    }
+    Ok((function_input_params, function_overloads))
 }
 
 /// Hashmap of all view fields with data types. I use it to construct the WHERE clause.
-/// Call it once on application start and store the result in a global variable.
-pub async fn get_for_cache_all_view_fields(db_pool: &deadpool_postgres::Pool) -> SqlViewFields {
+/// Call it on application start and store the result in the SqlMetadataCache.
+/// Fields with unknown data types are logged and skipped. They cannot be used in the WHERE clause.
+pub async fn get_for_cache_all_view_fields(
+    db_pool: &deadpool_postgres::Pool,
+) -> Result<SqlViewFields, LibError> {
     let query = "SELECT relname, attname, typname from get_view_fields order by relname;";
-    let vec_row = run_sql_select_query_pool(db_pool, query, &[])
-        .await
-        .unwrap();
+    let vec_row = run_sql_select_query_pool(db_pool, query, &[]).await?;
 
     let mut view_fields: SqlViewFields = HashMap::new();
     let mut hm_name_type = HashMap::new();
@@ -168,13 +322,39 @@ pub async fn get_for_cache_all_view_fields(db_pool: &deadpool_postgres::Pool) ->
         let typname: String = row.get(2);
         //dbg!(&typname);
         use std::str::FromStr;
-        let arg_type = PostgresFieldType::from_str(&typname).unwrap();
-        hm_name_type.insert(attname, arg_type);
+        match PostgresFieldType::from_str(&typname) {
+            Ok(arg_type) => {
+                hm_name_type.insert(attname, arg_type);
+            }
+            Err(_err) => log::warn!(
+                "Field {}.{} skipped: unknown data type: {}",
+                old_relname.0,
+                attname.0,
+                typname
+            ),
+        }
     }
     if !old_relname.0.is_empty() {
         //dbg!(&vec_name_type);
         view_fields.insert(old_relname, hm_name_type);
     }
     // dbg!(&view_fields);
-    view_fields
+    Ok(view_fields)
+}
+
+/// Hashmap of all roles with the allowed function and view names.
+/// Call it on application start and store the result in the SqlMetadataCache.
+pub async fn get_for_cache_all_role_permissions(
+    db_pool: &deadpool_postgres::Pool,
+) -> Result<SqlRolePermissions, LibError> {
+    let query = "SELECT role_name, object_name from app_role_permissions;";
+    let vec_row = run_sql_select_query_pool(db_pool, query, &[]).await?;
+    let mut role_permissions: SqlRolePermissions = HashMap::new();
+    for row in vec_row.iter() {
+        role_permissions
+            .entry(row.get(0))
+            .or_default()
+            .insert(row.get(1));
+    }
+    Ok(role_permissions)
 }
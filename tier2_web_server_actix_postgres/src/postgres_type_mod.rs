@@ -5,43 +5,415 @@
 // you implements traits. But for this simple example I don't need to complicate.
 // Postgres have many different names for data-types and it is confusing.
 
-/// PostgresInputType names as string come out of the view get_function_input_params and
+/// PostgresInputType comes from the type oid in the view get_function_input_params and
 /// is used for input params for functions.
+/// The names are used to cast the params in the function call: $1::character varying
 /// The names are strictly in lowercase, but Rust insist the enum variant are capitalized.
-/// snake case will be ok here, because we have always only 1 word
-#[derive(strum::AsRefStr, strum::EnumString, Debug)]
+/// Some names have more words and they are serialized explicitly.
+#[derive(strum::AsRefStr, strum::EnumString, Debug, Clone, Copy)]
 #[strum(serialize_all = "snake_case")]
 pub enum PostgresInputType {
+    /// 2 bytes
+    Smallint,
     /// 4 bytes
     Integer,
-    /// can be in 2 words "Character varying", but I use only the first word
+    /// 8 bytes
+    Bigint,
+    /// 4 bytes floating point
+    Real,
+    /// 8 bytes floating point
+    #[strum(serialize = "double precision")]
+    DoublePrecision,
+    /// exact decimal number
+    Numeric,
+    /// true or false
+    Boolean,
+    /// "character varying" is varchar and "character" is bpchar
+    #[strum(serialize = "character varying", serialize = "character")]
     Character,
     /// text (max 2GB)
     Text,
+    /// date without time
+    Date,
+    /// date and time
+    #[strum(serialize = "timestamp without time zone")]
+    Timestamp,
+    /// date and time with time zone
+    #[strum(serialize = "timestamp with time zone")]
+    TimestampTz,
+    /// universally unique identifier
+    Uuid,
+    /// json as text
+    Json,
+    /// json in binary format
+    Jsonb,
 }
 
 /// PostgresFieldType names as string come out of the view get_view_fields and
 /// is used for constructing the WHERE clause.
 /// The names are strictly in lowercase, but Rust insist the enum variant are capitalized.
 /// snake case will be ok here, because we have always only 1 word
-#[derive(strum::AsRefStr, strum::EnumString, Debug)]
+#[derive(strum::AsRefStr, strum::EnumString, Debug, Clone, Copy)]
 #[strum(serialize_all = "snake_case")]
 pub enum PostgresFieldType {
+    /// 2 bytes
+    Int2,
     /// 4 bytes
     Int4,
+    /// 8 bytes
+    Int8,
+    /// 4 bytes floating point
+    Float4,
+    /// 8 bytes floating point
+    Float8,
+    /// exact decimal number
+    Numeric,
+    /// true or false
+    Bool,
     /// varchar
     Varchar,
+    /// character with fixed length
+    Bpchar,
     /// names of postgres objects
     Name,
     /// text (max 2GB)
     Text,
+    /// date without time
+    Date,
+    /// date and time
+    Timestamp,
+    /// date and time with time zone
+    Timestamptz,
+    /// universally unique identifier
+    Uuid,
+    /// json as text
+    Json,
+    /// json in binary format
+    Jsonb,
+}
+
+impl PostgresInputType {
+    /// the data type of the function input param from the oid in pg_type
+    /// None for the data types that are not supported
+    pub fn from_oid(oid: u32) -> Option<PostgresInputType> {
+        use tokio_postgres::types::Type;
+        let input_type = match Type::from_oid(oid)? {
+            Type::INT2 => PostgresInputType::Smallint,
+            Type::INT4 => PostgresInputType::Integer,
+            Type::INT8 => PostgresInputType::Bigint,
+            Type::FLOAT4 => PostgresInputType::Real,
+            Type::FLOAT8 => PostgresInputType::DoublePrecision,
+            Type::NUMERIC => PostgresInputType::Numeric,
+            Type::BOOL => PostgresInputType::Boolean,
+            Type::VARCHAR | Type::BPCHAR => PostgresInputType::Character,
+            Type::TEXT => PostgresInputType::Text,
+            Type::DATE => PostgresInputType::Date,
+            Type::TIMESTAMP => PostgresInputType::Timestamp,
+            Type::TIMESTAMPTZ => PostgresInputType::TimestampTz,
+            Type::UUID => PostgresInputType::Uuid,
+            Type::JSON => PostgresInputType::Json,
+            Type::JSONB => PostgresInputType::Jsonb,
+            _ => return None,
+        };
+        Some(input_type)
+    }
+
+    /// Html forms send empty strings for empty fields.
+    /// For text types the empty string is a valid value,
+    /// for other types the empty string is sent to the function as NULL.
+    pub fn empty_string_is_null(&self) -> bool {
+        !matches!(self, PostgresInputType::Character | PostgresInputType::Text)
+    }
+
+    /// NULL value must have the data type of the input param
+    pub fn null_value(&self) -> PostgresValue {
+        match self {
+            PostgresInputType::Smallint => PostgresValue::I16(None),
+            PostgresInputType::Integer => PostgresValue::I32(None),
+            PostgresInputType::Bigint => PostgresValue::I64(None),
+            PostgresInputType::Real => PostgresValue::F32(None),
+            PostgresInputType::DoublePrecision => PostgresValue::F64(None),
+            PostgresInputType::Numeric => PostgresValue::Decimal(None),
+            PostgresInputType::Boolean => PostgresValue::Bool(None),
+            PostgresInputType::Character | PostgresInputType::Text => PostgresValue::String(None),
+            PostgresInputType::Date => PostgresValue::Date(None),
+            PostgresInputType::Timestamp => PostgresValue::Timestamp(None),
+            PostgresInputType::TimestampTz => PostgresValue::TimestampTz(None),
+            PostgresInputType::Uuid => PostgresValue::Uuid(None),
+            PostgresInputType::Json | PostgresInputType::Jsonb => PostgresValue::Json(None),
+        }
+    }
+}
+
+impl PostgresFieldType {
+    /// the params in the WHERE clause are cast like input params of the same type
+    pub fn input_type(&self) -> PostgresInputType {
+        match self {
+            PostgresFieldType::Int2 => PostgresInputType::Smallint,
+            PostgresFieldType::Int4 => PostgresInputType::Integer,
+            PostgresFieldType::Int8 => PostgresInputType::Bigint,
+            PostgresFieldType::Float4 => PostgresInputType::Real,
+            PostgresFieldType::Float8 => PostgresInputType::DoublePrecision,
+            PostgresFieldType::Numeric => PostgresInputType::Numeric,
+            PostgresFieldType::Bool => PostgresInputType::Boolean,
+            PostgresFieldType::Varchar | PostgresFieldType::Bpchar => PostgresInputType::Character,
+            PostgresFieldType::Name | PostgresFieldType::Text => PostgresInputType::Text,
+            PostgresFieldType::Date => PostgresInputType::Date,
+            PostgresFieldType::Timestamp => PostgresInputType::Timestamp,
+            PostgresFieldType::Timestamptz => PostgresInputType::TimestampTz,
+            PostgresFieldType::Uuid => PostgresInputType::Uuid,
+            PostgresFieldType::Json => PostgresInputType::Json,
+            PostgresFieldType::Jsonb => PostgresInputType::Jsonb,
+        }
+    }
 }
 
 /// PostgresValue can contain values of different data types.
 /// For this simple example this is easier then implementing traits for every type.
 /// I want deliberately limit the use to just a few data types for simplicity.
+/// None is NULL in the database.
 #[derive(Debug)]
 pub enum PostgresValue {
-    String(String),
-    I32(i32),
+    I16(Option<i16>),
+    I32(Option<i32>),
+    I64(Option<i64>),
+    F32(Option<f32>),
+    F64(Option<f64>),
+    Decimal(Option<rust_decimal::Decimal>),
+    Bool(Option<bool>),
+    String(Option<String>),
+    Date(Option<chrono::NaiveDate>),
+    Timestamp(Option<chrono::NaiveDateTime>),
+    TimestampTz(Option<chrono::DateTime<chrono::Utc>>),
+    Uuid(Option<uuid::Uuid>),
+    Json(Option<serde_json::Value>),
+}
+
+impl PostgresValue {
+    /// reference to the inner value in the format expected by the postgres library
+    pub fn as_to_sql(&self) -> &(dyn tokio_postgres::types::ToSql + Sync) {
+        match self {
+            PostgresValue::I16(x) => x,
+            PostgresValue::I32(x) => x,
+            PostgresValue::I64(x) => x,
+            PostgresValue::F32(x) => x,
+            PostgresValue::F64(x) => x,
+            PostgresValue::Decimal(x) => x,
+            PostgresValue::Bool(x) => x,
+            PostgresValue::String(x) => x,
+            PostgresValue::Date(x) => x,
+            PostgresValue::Timestamp(x) => x,
+            PostgresValue::TimestampTz(x) => x,
+            PostgresValue::Uuid(x) => x,
+            PostgresValue::Json(x) => x,
+        }
+    }
+
+    /// the value in the same json format as the values of the rows
+    pub fn to_json(&self) -> serde_json::Value {
+        use serde_json::Value;
+        fn or_null<T>(x: &Option<T>, to_json: impl Fn(&T) -> Value) -> Value {
+            x.as_ref().map_or(Value::Null, to_json)
+        }
+        match self {
+            PostgresValue::I16(x) => or_null(x, |x| Value::from(*x)),
+            PostgresValue::I32(x) => or_null(x, |x| Value::from(*x)),
+            PostgresValue::I64(x) => or_null(x, |x| Value::from(*x)),
+            PostgresValue::F32(x) => or_null(x, |x| Value::from(*x)),
+            PostgresValue::F64(x) => or_null(x, |x| Value::from(*x)),
+            PostgresValue::Decimal(x) => or_null(x, |x| Value::from(x.to_string())),
+            PostgresValue::Bool(x) => or_null(x, |x| Value::from(*x)),
+            PostgresValue::String(x) => or_null(x, |x| Value::from(x.as_str())),
+            PostgresValue::Date(x) => or_null(x, |x| Value::from(x.format("%Y-%m-%d").to_string())),
+            PostgresValue::Timestamp(x) => or_null(x, |x| {
+                Value::from(x.format("%Y-%m-%dT%H:%M:%S%.f").to_string())
+            }),
+            PostgresValue::TimestampTz(x) => or_null(x, |x| Value::from(x.to_rfc3339())),
+            PostgresValue::Uuid(x) => or_null(x, |x| Value::from(x.to_string())),
+            PostgresValue::Json(x) => or_null(x, |x| x.clone()),
+        }
+    }
+}
+
+/// bytea is rendered as hex like in psql: \x0a1b
+struct ByteaHex(Vec<u8>);
+
+impl<'a> tokio_postgres::types::FromSql<'a> for ByteaHex {
+    fn from_sql(
+        ty: &tokio_postgres::types::Type,
+        raw: &'a [u8],
+    ) -> Result<Self, Box<dyn std::error::Error + Sync + Send>> {
+        Ok(ByteaHex(Vec::<u8>::from_sql(ty, raw)?))
+    }
+    tokio_postgres::types::accepts!(BYTEA);
+}
+
+impl std::fmt::Display for ByteaHex {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "\\x")?;
+        for byte in self.0.iter() {
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+/// the value from a Row column as string for html or other text formats
+/// NULL is returned as None. Arrays are formatted like in postgres: {1,2,NULL}
+/// postgres has a very long list of types. I will use only a subset of them.
+pub fn row_value_to_string(
+    row: &tokio_postgres::Row,
+    i: usize,
+) -> Result<Option<String>, crate::error_mod::LibError> {
+    use tokio_postgres::types::{Kind, Type};
+    let col_type = row.columns()[i].type_();
+    // the same rust types are used for the elements of the arrays
+    let (element_type, is_array) = match col_type.kind() {
+        Kind::Array(element_type) => (element_type, true),
+        _ => (col_type, false),
+    };
+    match *element_type {
+        Type::BOOL => get_as_string::<bool>(row, i, is_array),
+        Type::INT2 => get_as_string::<i16>(row, i, is_array),
+        Type::INT4 => get_as_string::<i32>(row, i, is_array),
+        Type::INT8 => get_as_string::<i64>(row, i, is_array),
+        Type::FLOAT4 => get_as_string::<f32>(row, i, is_array),
+        Type::FLOAT8 => get_as_string::<f64>(row, i, is_array),
+        Type::NUMERIC => get_as_string::<rust_decimal::Decimal>(row, i, is_array),
+        Type::TEXT | Type::VARCHAR | Type::BPCHAR | Type::NAME => {
+            get_as_string::<String>(row, i, is_array)
+        }
+        Type::DATE => get_as_string::<chrono::NaiveDate>(row, i, is_array),
+        Type::TIMESTAMP => get_as_string::<chrono::NaiveDateTime>(row, i, is_array),
+        Type::TIMESTAMPTZ => get_as_string::<chrono::DateTime<chrono::Utc>>(row, i, is_array),
+        Type::UUID => get_as_string::<uuid::Uuid>(row, i, is_array),
+        Type::JSON | Type::JSONB => get_as_string::<serde_json::Value>(row, i, is_array),
+        Type::BYTEA => get_as_string::<ByteaHex>(row, i, is_array),
+        // void function returns a Row with one column of type void
+        Type::VOID => Ok(None),
+        _ => Err(crate::error_mod::LibError::UnsupportedPostgresType {
+            user_friendly: col_type.to_string(),
+            developer_friendly: format!("column: {}", row.columns()[i].name()),
+            source_line_column: format!("{}:{}:{}", file!(), line!(), column!()),
+        }),
+    }
+}
+
+/// get the value from the Row as a rust type and format it as string
+fn get_as_string<T>(
+    row: &tokio_postgres::Row,
+    i: usize,
+    is_array: bool,
+) -> Result<Option<String>, crate::error_mod::LibError>
+where
+    T: for<'a> tokio_postgres::types::FromSql<'a> + std::fmt::Display,
+{
+    let map_err =
+        |err: tokio_postgres::Error| crate::error_mod::LibError::UnsupportedPostgresType {
+            user_friendly: row.columns()[i].type_().to_string(),
+            developer_friendly: format!("column: {} {}", row.columns()[i].name(), err),
+            source_line_column: format!("{}:{}:{}", file!(), line!(), column!()),
+        };
+    if is_array {
+        let value: Option<Vec<Option<T>>> = row.try_get(i).map_err(map_err)?;
+        Ok(value.map(|vec| {
+            let elements: Vec<String> = vec
+                .iter()
+                .map(|x| x.as_ref().map_or("NULL".to_string(), |x| x.to_string()))
+                .collect();
+            format!("{{{}}}", elements.join(","))
+        }))
+    } else {
+        let value: Option<T> = row.try_get(i).map_err(map_err)?;
+        Ok(value.map(|x| x.to_string()))
+    }
+}
+
+/// all the columns of the Row as a json object with column names as keys
+pub fn row_to_json(
+    row: &tokio_postgres::Row,
+) -> Result<serde_json::Value, crate::error_mod::LibError> {
+    let mut object = serde_json::Map::new();
+    for (i, col) in row.columns().iter().enumerate() {
+        object.insert(col.name().to_string(), row_value_to_json(row, i)?);
+    }
+    Ok(serde_json::Value::Object(object))
+}
+
+/// the value from a Row column as json value of the corresponding type
+/// NULL is json null. Arrays are json arrays.
+/// numeric is a json string, because json numbers would lose precision
+pub fn row_value_to_json(
+    row: &tokio_postgres::Row,
+    i: usize,
+) -> Result<serde_json::Value, crate::error_mod::LibError> {
+    use serde_json::Value;
+    use tokio_postgres::types::{Kind, Type};
+    let col_type = row.columns()[i].type_();
+    // the same rust types are used for the elements of the arrays
+    let (element_type, is_array) = match col_type.kind() {
+        Kind::Array(element_type) => (element_type, true),
+        _ => (col_type, false),
+    };
+    match *element_type {
+        Type::BOOL => get_as_json::<bool>(row, i, is_array, Value::from),
+        Type::INT2 => get_as_json::<i16>(row, i, is_array, Value::from),
+        Type::INT4 => get_as_json::<i32>(row, i, is_array, Value::from),
+        Type::INT8 => get_as_json::<i64>(row, i, is_array, Value::from),
+        Type::FLOAT4 => get_as_json::<f32>(row, i, is_array, Value::from),
+        Type::FLOAT8 => get_as_json::<f64>(row, i, is_array, Value::from),
+        Type::NUMERIC => {
+            get_as_json::<rust_decimal::Decimal>(row, i, is_array, |x| Value::from(x.to_string()))
+        }
+        Type::TEXT | Type::VARCHAR | Type::BPCHAR | Type::NAME => {
+            get_as_json::<String>(row, i, is_array, Value::from)
+        }
+        Type::DATE => get_as_json::<chrono::NaiveDate>(row, i, is_array, |x| {
+            Value::from(x.format("%Y-%m-%d").to_string())
+        }),
+        Type::TIMESTAMP => get_as_json::<chrono::NaiveDateTime>(row, i, is_array, |x| {
+            Value::from(x.format("%Y-%m-%dT%H:%M:%S%.f").to_string())
+        }),
+        Type::TIMESTAMPTZ => get_as_json::<chrono::DateTime<chrono::Utc>>(row, i, is_array, |x| {
+            Value::from(x.to_rfc3339())
+        }),
+        Type::UUID => get_as_json::<uuid::Uuid>(row, i, is_array, |x| Value::from(x.to_string())),
+        Type::JSON | Type::JSONB => get_as_json::<Value>(row, i, is_array, |x| x),
+        Type::BYTEA => get_as_json::<ByteaHex>(row, i, is_array, |x| Value::from(x.to_string())),
+        // void function returns a Row with one column of type void
+        Type::VOID => Ok(Value::Null),
+        _ => Err(crate::error_mod::LibError::UnsupportedPostgresType {
+            user_friendly: col_type.to_string(),
+            developer_friendly: format!("column: {}", row.columns()[i].name()),
+            source_line_column: format!("{}:{}:{}", file!(), line!(), column!()),
+        }),
+    }
+}
+
+/// get the value from the Row as a rust type and convert it to json
+fn get_as_json<T>(
+    row: &tokio_postgres::Row,
+    i: usize,
+    is_array: bool,
+    to_json: fn(T) -> serde_json::Value,
+) -> Result<serde_json::Value, crate::error_mod::LibError>
+where
+    T: for<'a> tokio_postgres::types::FromSql<'a>,
+{
+    let map_err =
+        |err: tokio_postgres::Error| crate::error_mod::LibError::UnsupportedPostgresType {
+            user_friendly: row.columns()[i].type_().to_string(),
+            developer_friendly: format!("column: {} {}", row.columns()[i].name(), err),
+            source_line_column: format!("{}:{}:{}", file!(), line!(), column!()),
+        };
+    let to_json_or_null = |x: Option<T>| x.map_or(serde_json::Value::Null, to_json);
+    if is_array {
+        let value: Option<Vec<Option<T>>> = row.try_get(i).map_err(map_err)?;
+        Ok(value.map_or(serde_json::Value::Null, |vec| {
+            serde_json::Value::Array(vec.into_iter().map(to_json_or_null).collect())
+        }))
+    } else {
+        let value: Option<T> = row.try_get(i).map_err(map_err)?;
+        Ok(to_json_or_null(value))
+    }
 }
@@ -0,0 +1,345 @@
+// server_side_import_mod.rs
+
+// Structs and methods for the import of many rows from a csv file
+// into the insert function of the scope: {scope}_insert
+// 1. read the uploaded csv file from the multipart form
+// 2. parse the csv and check that the header has only the input params of the sql function
+// 3. call the sql function for every row inside one transaction.
+//    Every row has a savepoint, so all the errors are collected in the report.
+// 4. commit if all rows are inserted, else rollback all the rows
+// 5. read html template {scope}_import_report and mix it with the report
+// 6. return a response with no cache
+// For json requests the steps 5. and 6. are replaced with the serialization of the report to json.
+
+use std::collections::HashMap;
+
+use crate::actix_mod::{DataAppState, ResponseFormat, ResultResponse, WebRequest};
+use crate::audit_mod::AuditCall;
+use crate::error_mod::LibError;
+use crate::postgres_mod::FunctionName;
+use crate::server_side_single_row_mod::ServerSideSingleRow;
+use crate::web_params_mod::WebParams;
+
+/// the server does not accept bigger files
+const MAX_IMPORT_FILE_SIZE: usize = 10 * 1024 * 1024;
+
+/// the result of the import for the report
+pub struct ImportReport {
+    pub row_count: usize,
+    /// the rows are imported only if there is no error
+    pub imported: bool,
+    /// the row number (without the header) and the error message
+    pub errors: Vec<(usize, String)>,
+}
+
+/// the main ServerSideImport object (struct with implementation)
+pub struct ServerSideImport<'a> {
+    app_state: &'a DataAppState,
+    req: &'a WebRequest,
+    scope: &'a str,
+    response_format: ResponseFormat,
+    function_name: FunctionName,
+}
+
+impl<'a> ServerSideImport<'a> {
+    /// constructor for the import into the sql function
+    pub fn new(
+        app_state: &'a DataAppState,
+        req: &'a WebRequest,
+        scope: &'a str,
+        function_name: &str,
+    ) -> Result<ServerSideImport<'a>, actix_web::Error> {
+        println!(
+            "{} {} import",
+            crate::error_mod::time_epoch(),
+            function_name
+        );
+        let response_format = ResponseFormat::from_request(req);
+        // the role of the user must allow the function that inserts the rows
+        crate::auth_mod::check_permission(app_state, req, function_name)
+            .map_err(|err| response_format.error(err))?;
+        Ok(ServerSideImport {
+            app_state,
+            req,
+            scope,
+            response_format,
+            function_name: FunctionName(function_name.to_string()),
+        })
+    }
+
+    /// the html page with the upload form from the template {scope}_import
+    pub fn run_import_form(&self) -> ResultResponse {
+        let template = self
+            .app_state
+            .html_templates
+            .get(self.scope, &format!("{}_import", self.scope))
+            .map_err(|err| self.response_format.error(err))?;
+        let body = template
+            .text
+            .replace("{function_name}", &self.function_name.0)
+            .replace("{csrf_token}", &crate::csrf_mod::csrf_token(self.req));
+        Ok(crate::actix_mod::return_response_no_cache(body))
+    }
+
+    /// typical steps for the import of a csv file
+    pub async fn run_import_and_process_html(
+        &self,
+        payload: actix_multipart::Multipart,
+    ) -> ResultResponse {
+        self.run_import_steps(payload)
+            .await
+            .map_err(|err| self.response_format.error(err))
+    }
+
+    /// the steps return LibError, so the error can be rendered as html or json
+    async fn run_import_steps(
+        &self,
+        payload: actix_multipart::Multipart,
+    ) -> Result<actix_web::HttpResponse, LibError> {
+        // region: 1. read the uploaded csv file from the multipart form
+        let (csv_text, csrf_token) = read_uploaded_file(payload).await?;
+        // the import modifies data and needs POST with the csrf token
+        crate::csrf_mod::verify_csrf_token(self.req, csrf_token.as_deref())?;
+        // endregion
+
+        // region: 2. parse the csv and check the header
+        let mut records = crate::csv_mod::parse_csv(&csv_text)
+            .map_err(|err| invalid_file(&format!("the file is not valid csv: {err}")))?;
+        if records.is_empty() {
+            return Err(invalid_file("the file is empty"));
+        }
+        let header = records.remove(0);
+        self.check_header(&header)?;
+        // endregion
+
+        // region: 3. and 4. call the sql function for every row inside one transaction
+        let report = self.run_sql_import(&header, &records).await?;
+        // endregion
+
+        if self.response_format == ResponseFormat::Json {
+            let errors: Vec<serde_json::Value> = report
+                .errors
+                .iter()
+                .map(|(row_number, error_message)| {
+                    serde_json::json!({"row_number": row_number, "error_message": error_message})
+                })
+                .collect();
+            let value = serde_json::json!({
+                "function_name": self.function_name.0,
+                "row_count": report.row_count,
+                "imported": report.imported,
+                "errors": errors,
+            });
+            return Ok(crate::actix_mod::return_json_response_no_cache(value));
+        }
+
+        // region: 5. read html template and mix it with the report
+        let body = self.template_replace_report(&report)?;
+        // endregion
+
+        // region: 6. return a response with no cache
+        Ok(crate::actix_mod::return_response_no_cache(body))
+        // endregion
+    }
+
+    /// all the columns in the header must be input params of the sql function
+    /// the missing params are reported as errors for every row
+    /// For an overloaded function, the column must be a param of any overload.
+    fn check_header(&self, header: &[String]) -> Result<(), LibError> {
+        let function_overloads = self
+            .app_state
+            .sql_metadata
+            .get()
+            .function_overloads(&self.function_name)?;
+        for column in header {
+            if !function_overloads
+                .iter()
+                .flat_map(|x| x.params.iter())
+                .any(|x| x.param_name.web_param_name() == column)
+            {
+                return Err(invalid_file(&format!(
+                    "the column {column} is not a param of {}",
+                    self.function_name.0
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// insert all the rows in one transaction or none of them
+    pub async fn run_sql_import(
+        &self,
+        header: &[String],
+        records: &[Vec<String>],
+    ) -> Result<ImportReport, LibError> {
+        let map_err = |err| crate::postgres_mod::sql_error_to_lib_error(err, "transaction", &[]);
+        let mut postgres_client =
+            crate::deadpool_mod::get_postgres_client_from_pool(&self.app_state.db_pool).await?;
+        let mut transaction = postgres_client.transaction().await.map_err(map_err)?;
+        crate::postgres_mod::set_local_app_user_id(
+            &*transaction,
+            crate::auth_mod::app_user_id(self.req),
+        )
+        .await?;
+        let mut errors = vec![];
+        let mut audit_calls = vec![];
+        for (i, record) in records.iter().enumerate() {
+            let row_number = i + 1;
+            if record.len() != header.len() {
+                errors.push((
+                    row_number,
+                    format!(
+                        "the row has {} values, but the header has {} columns",
+                        record.len(),
+                        header.len()
+                    ),
+                ));
+                continue;
+            }
+            let web_params = WebParams(
+                header
+                    .iter()
+                    .cloned()
+                    .zip(record.iter().cloned())
+                    .collect::<HashMap<String, String>>(),
+            );
+            let mut sssr = ServerSideSingleRow::from_web_params(
+                self.app_state,
+                self.req,
+                self.scope,
+                &self.function_name.0,
+                web_params,
+            );
+            // the error in one row must not abort the transaction for the next rows
+            let savepoint = transaction.savepoint("import_row").await.map_err(map_err)?;
+            let result = match sssr.prepare_function_params() {
+                Ok(()) => {
+                    let function_overload = sssr.function_overload()?;
+                    let sql_params = sssr.ref_to_function_params();
+                    let result = sssr
+                        .run_sql_single_row_function_on_client(
+                            &*savepoint,
+                            function_overload,
+                            sql_params,
+                        )
+                        .await;
+                    audit_calls.push(AuditCall::new(
+                        &self.function_name,
+                        Some(function_overload),
+                        sssr.sql_params(),
+                        result.as_ref().map(|_| ()),
+                        true,
+                    ));
+                    result
+                }
+                Err(err) => Err(err),
+            };
+            match result {
+                Ok(_row) => savepoint.commit().await.map_err(map_err)?,
+                Err(err) => {
+                    savepoint.rollback().await.map_err(map_err)?;
+                    errors.push((row_number, err.to_string()));
+                }
+            }
+        }
+        let imported = errors.is_empty();
+        if imported {
+            transaction.commit().await.map_err(map_err)?;
+        } else {
+            transaction.rollback().await.map_err(map_err)?;
+        }
+        crate::audit_mod::insert_audit_log(self.app_state, self.req, &audit_calls, !imported).await;
+        Ok(ImportReport {
+            row_count: records.len(),
+            imported,
+            errors,
+        })
+    }
+
+    /// the template {scope}_import_report has the placeholders:
+    /// {function_name}, {row_count}, {error_count}, {import_result}
+    /// and the fragment for every error with {row_number}, {error_message}
+    fn template_replace_report(&self, report: &ImportReport) -> Result<String, LibError> {
+        let template_name = format!("{}_import_report", self.scope);
+        let template = self
+            .app_state
+            .html_templates
+            .get(self.scope, &template_name)?;
+        let row_fragment = template.row_fragment(&template_name)?;
+        let fragment_for_single_row =
+            &template.text[row_fragment.start_inner..row_fragment.end_inner];
+
+        let mut replaced_errors = String::new();
+        for (row_number, error_message) in report.errors.iter() {
+            replaced_errors.push_str(
+                &fragment_for_single_row
+                    .replace("{row_number}", &row_number.to_string())
+                    .replace("{error_message}", &html_escape::encode_text(error_message)),
+            );
+        }
+        let mut body = template.text.clone();
+        body.replace_range(
+            row_fragment.start_outer..row_fragment.end_outer,
+            &replaced_errors,
+        );
+
+        let import_result = if report.imported {
+            format!("All {} rows are imported.", report.row_count)
+        } else {
+            "Nothing is imported. Correct the errors and upload the file again.".to_string()
+        };
+        Ok(body
+            .replace("{function_name}", &self.function_name.0)
+            .replace("{row_count}", &report.row_count.to_string())
+            .replace("{error_count}", &report.errors.len().to_string())
+            .replace("{import_result}", &import_result))
+    }
+}
+
+/// read the field "file" from the multipart form as text
+/// and the field "csrf_token" if it exists
+async fn read_uploaded_file(
+    mut payload: actix_multipart::Multipart,
+) -> Result<(String, Option<String>), LibError> {
+    use futures_util::TryStreamExt;
+    let mut bytes = vec![];
+    let mut csrf_token = None;
+    while let Some(mut field) = payload
+        .try_next()
+        .await
+        .map_err(|err| invalid_file(&err.to_string()))?
+    {
+        let field_name = field.name().to_string();
+        if field_name != "file" && field_name != crate::csrf_mod::CSRF_TOKEN_NAME {
+            continue;
+        }
+        let mut field_bytes = vec![];
+        while let Some(chunk) = field
+            .try_next()
+            .await
+            .map_err(|err| invalid_file(&err.to_string()))?
+        {
+            if field_bytes.len() + chunk.len() > MAX_IMPORT_FILE_SIZE {
+                return Err(invalid_file("the file is too big"));
+            }
+            field_bytes.extend_from_slice(&chunk);
+        }
+        if field_name == "file" {
+            bytes = field_bytes;
+        } else {
+            csrf_token = Some(String::from_utf8_lossy(&field_bytes).to_string());
+        }
+    }
+    let csv_text =
+        String::from_utf8(bytes).map_err(|_| invalid_file("the file is not UTF-8 text"))?;
+    Ok((csv_text, csrf_token))
+}
+
+/// the uploaded file cannot be imported
+fn invalid_file(message: &str) -> LibError {
+    LibError::InvalidWebParam {
+        user_friendly: format!("file: {message}"),
+        developer_friendly: message.to_string(),
+        source_line_column: format!("{}:{}:{}", file!(), line!(), column!()),
+    }
+}
@@ -4,12 +4,17 @@
 // for multi row: (list):
 // 1. parse web data: strings coming from the browser in path, query and form
 // 2. these are filter data for WHERE and ORDER_BY from the parameters
+//    and page, page_size for LIMIT and OFFSET
 // 3. cast the web data to call the sql statement with correct data types
-// 4. retrieve sql data as vector of rows
+// 4. retrieve the count of all rows and the sql data of one page as vector of rows
 // 5. read html template (presentation) from disk or cache
 // 6. extract the <!--row_start--> and <!--row_end--> fragment, that will be repeated
 // 7. mix presentation and data, because this is server-side rendering
 // 8. return a response with no cache (because data in database can change fast)
+// For json requests the steps 5. to 7. are replaced with the serialization of the rows to json.
+// The export of all the rows without pagination streams the rows in csv or tsv format.
+// The statements run inside a read-only transaction with the local setting app.user_id
+// for the row-level security policies in postgres.
 
 // TODO: dynamically construct a where clause only for the used filters for efficiency
 // TODO: dynamically construct the fields list only for fields used in the html (for efficiency)
@@ -17,25 +22,117 @@
 use lazy_static::lazy_static;
 use regex::Regex;
 
-use crate::actix_mod::{DataAppState, ResultResponse, WebForm, WebQuery};
+use crate::actix_mod::WebRequest;
+use crate::actix_mod::{DataAppState, ResponseFormat, ResultResponse, WebForm, WebQuery};
+use crate::error_mod::LibError;
 use crate::postgres_mod::{FieldName, ViewName};
-use crate::postgres_type_mod::PostgresValue;
+use crate::postgres_type_mod::{PostgresFieldType, PostgresValue};
 use crate::web_params_mod::WebParams;
 
 lazy_static! {
     static ref RGX_01: Regex = Regex::new(r###"\{(.+?)}"###).unwrap();
+    /// filter placeholders in the template start with f_
+    static ref RGX_02: Regex = Regex::new(r###"\{f_\w+}"###).unwrap();
+}
+
+/// number of rows in one page, if the web param page_size is not defined
+const DEFAULT_PAGE_SIZE: i64 = 50;
+/// the server never returns more rows in one page, regardless of the web param page_size
+const MAX_PAGE_SIZE: i64 = 500;
+/// the key field of the view, in the same format as the web param f_order_by
+const DEFAULT_ORDER_BY: &str = "id";
+
+/// one field in the ORDER BY clause
+/// web param: f_order_by=webpage,-hit_count:nulls_last
+/// the minus prefix is for descending order, the suffix is for the order of NULL values
+pub struct OrderByField {
+    pub field_name: FieldName,
+    pub descending: bool,
+    pub nulls: Option<NullsOrder>,
+}
+
+/// NULL values can be ordered before or after other values
+#[derive(Clone, Copy)]
+pub enum NullsOrder {
+    First,
+    Last,
+}
+
+impl OrderByField {
+    /// parse one field from the web param f_order_by
+    /// returns None if the syntax is not correct
+    pub fn parse(text: &str) -> Option<OrderByField> {
+        let (text, nulls) = match text.split_once(':') {
+            Some((text, "nulls_first")) => (text, Some(NullsOrder::First)),
+            Some((text, "nulls_last")) => (text, Some(NullsOrder::Last)),
+            Some(_) => return None,
+            None => (text, None),
+        };
+        let (field_name, descending) = match text.strip_prefix('-') {
+            Some(field_name) => (field_name, true),
+            None => (text, false),
+        };
+        if field_name.is_empty() {
+            return None;
+        }
+        Some(OrderByField {
+            field_name: FieldName(field_name.to_string()),
+            descending,
+            nulls,
+        })
+    }
+
+    /// sql for the ORDER BY clause with the field name quoted as identifier
+    pub fn to_sql(&self) -> String {
+        let mut sql = crate::postgres_mod::quote_identifier(&self.field_name.0);
+        if self.descending {
+            sql.push_str(" DESC");
+        }
+        match self.nulls {
+            Some(NullsOrder::First) => sql.push_str(" NULLS FIRST"),
+            Some(NullsOrder::Last) => sql.push_str(" NULLS LAST"),
+            None => (),
+        }
+        sql
+    }
+
+    /// the same format as in the web param f_order_by
+    pub fn to_web_param(&self) -> String {
+        let mut text = String::new();
+        if self.descending {
+            text.push('-');
+        }
+        text.push_str(&self.field_name.0);
+        match self.nulls {
+            Some(NullsOrder::First) => text.push_str(":nulls_first"),
+            Some(NullsOrder::Last) => text.push_str(":nulls_last"),
+            None => (),
+        }
+        text
+    }
 }
 
 /// the main ServerSideMultiRow object (struct with implementation)
 pub struct ServerSideMultiRow<'a> {
     app_state: &'a DataAppState,
     scope: &'a str,
+    response_format: ResponseFormat,
+    /// the token for the placeholder {csrf_token} in forms that modify data
+    csrf_token: String,
     view_name: ViewName,
     web_params: WebParams,
     sql_params: Vec<PostgresValue>,
     pub where_clause: Vec<&'static str>,
     sql_where: String,
     sql_order_by: String,
+    order_by: Vec<OrderByField>,
+    /// the order without f_order_by and the last field for stable pages: "-id"
+    pub default_order_by: &'static str,
+    page: i64,
+    page_size: i64,
+    row_count: i64,
+    /// the logged in user for the row-level security policies
+    app_user_id: Option<i32>,
 }
 
 impl<'a> ServerSideMultiRow<'a> {
@@ -44,53 +141,112 @@ impl<'a> ServerSideMultiRow<'a> {
     #[track_caller]
     pub fn new(
         app_state: &'a DataAppState,
-        scope: &'static str,
-        view_name: &'static str,
+        req: &'a WebRequest,
+        scope: &'a str,
+        view_name: &str,
         query: &'a WebQuery,
         form: &'a Option<WebForm>,
-    ) -> ServerSideMultiRow<'a> {
+    ) -> Result<ServerSideMultiRow<'a>, actix_web::Error> {
         println!("{} {}", crate::error_mod::time_epoch(), view_name);
+        let response_format = ResponseFormat::from_request(req);
         // region: 1. parse web data: strings coming from the browser in path, query and form
-        let web_params = WebParams::from_actix(query, form);
+        // the views are read-only and don't need the csrf token
+        let web_params = WebParams::from_actix(req, query, form, false)
+            .map_err(|err| response_format.error(err))?;
         // endregion
 
-        ServerSideMultiRow {
+        // the role of the user must allow this view
+        crate::auth_mod::check_permission(app_state, req, view_name)
+            .map_err(|err| response_format.error(err))?;
+
+        Ok(ServerSideMultiRow {
             app_state,
             scope,
+            response_format,
+            csrf_token: crate::csrf_mod::csrf_token(req),
             view_name: ViewName(view_name.to_string()),
             web_params,
             sql_params: vec![],
             where_clause: vec![],
             sql_where: String::new(),
             sql_order_by: String::new(),
-        }
+            order_by: vec![],
+            default_order_by: DEFAULT_ORDER_BY,
+            page: 1,
+            page_size: DEFAULT_PAGE_SIZE,
+            row_count: 0,
+            app_user_id: crate::auth_mod::app_user_id(req),
+        })
     }
 
     /// typical steps for a web app function for multi Row sql statement
     /// These steps can be called separately if some customization is needed
     pub async fn run_multi_row_sql_and_process_html(&mut self) -> ResultResponse {
-        // region: 2. find out the filters from the parameters
-        self.prepare_filter_params();
+        let response_format = self.response_format;
+        self.run_multi_row_steps()
+            .await
+            .map_err(|err| response_format.error(err))
+    }
+
+    /// the steps return LibError, so the error can be rendered as html or json
+    async fn run_multi_row_steps(&mut self) -> Result<actix_web::HttpResponse, LibError> {
+        // region: 2. find out the filters and the page from the parameters
+        self.prepare_filter_params()?;
+        self.prepare_pagination()?;
         // endregion
 
         // region: 3. cast the web data to call the function with correct data types
         let sql_params = self.ref_to_function_params();
         // endregion
 
-        // region: 4. retrieve sql data as vector of rows
-        let multi_row = self.run_sql_multi_row_statement(sql_params).await;
+        // region: 4. retrieve the count of all rows and the sql data of one page as vector of rows
+        let map_err = |err| crate::postgres_mod::sql_error_to_lib_error(err, "transaction", &[]);
+        let mut postgres_client =
+            crate::deadpool_mod::get_postgres_client_from_pool(&self.app_state.db_pool).await?;
+        let transaction = postgres_client
+            .build_transaction()
+            .read_only(true)
+            .start()
+            .await
+            .map_err(map_err)?;
+        crate::postgres_mod::set_local_app_user_id(&*transaction, self.app_user_id).await?;
+        let row_count = self
+            .run_sql_count_statement(&*transaction, &sql_params)
+            .await?;
+        let multi_row = self
+            .run_sql_multi_row_statement(&*transaction, sql_params)
+            .await?;
+        transaction.commit().await.map_err(map_err)?;
+        self.row_count = row_count;
         // endregion
 
+        if self.response_format == ResponseFormat::Json {
+            let rows = multi_row
+                .iter()
+                .map(crate::postgres_type_mod::row_to_json)
+                .collect::<Result<Vec<_>, LibError>>()?;
+            let value = serde_json::json!({
+                "page": self.page,
+                "page_size": self.page_size,
+                "page_count": self.page_count(),
+                "row_count": self.row_count,
+                "rows": rows,
+            });
+            return Ok(crate::actix_mod::return_json_response_no_cache(value));
+        }
+
         // region: 5. read html template (presentation) from disk or cache
-        let mut body = crate::html_templating_mod::read_template(self.scope, &self.view_name.0);
+        let template = self
+            .app_state
+            .html_templates
+            .get(self.scope, &self.view_name.0)?;
         // endregion
 
         // region: 6. extract the fragment from <!--row_start--> to <!--row_end-->. It will be repeated for every Row.
-        let row_start_outer = body.find("<!--row_start-->").unwrap();
-        let row_start_inner = row_start_outer + "<!--row_start-->".len();
-        let row_end_inner = body.find("<!--row_end-->").unwrap();
-        let row_end_outer = row_end_inner + "<!--row_end-->".len();
-        let fragment_for_single_row = &body[row_start_inner..row_end_inner];
+        // the position of the fragment is located once, when the template is read
+        let row_fragment = template.row_fragment(&self.view_name.0)?;
+        let fragment_for_single_row =
+            &template.text[row_fragment.start_inner..row_fragment.end_inner];
         // endregion
 
         // region: 7. mix presentation and data, because this is server-side rendering
@@ -100,29 +256,130 @@ impl<'a> ServerSideMultiRow<'a> {
                 crate::html_templating_mod::template_replace_fields_from_single_row(
                     fragment_for_single_row,
                     single_row,
-                );
+                    &self.app_state.html_null_marker,
+                )?;
             replaced_with_multi_row.push_str(&replaced_fragment);
         }
 
-        body.replace_range(row_start_outer..row_end_outer, &replaced_with_multi_row);
+        let mut body = template.text.clone();
+        body.replace_range(
+            row_fragment.start_outer..row_fragment.end_outer,
+            &replaced_with_multi_row,
+        );
+
+        // the pager links and info
+        body = self.template_replace_pagination(&body);
+        // the links to sort by column
+        body = self.template_replace_sorting(&body);
+        // the links to export all the rows
+        body = self.template_replace_export(&body);
+        // the delete buttons in the list are forms with the csrf token
+        body = body.replace("{csrf_token}", &self.csrf_token);
 
         // replace the filter fields from the input web_params
         // if there are not input web_params then find and replace with empty
         for (name, value) in self.web_params.0.iter() {
             let from = format!("{{{}}}", name);
-            body = body.replace(&from, value);
+            body = body.replace(&from, &html_escape::encode_double_quoted_attribute(value));
         }
+        body = RGX_02.replace_all(&body, "").to_string();
         // endregion
 
         // region: 8. return a response with no cache (because data in database can change fast)
-        crate::actix_mod::return_response_no_cache(body)
+        Ok(crate::actix_mod::return_response_no_cache(body))
+        // endregion
+    }
+
+    /// steps for the export of all filtered and ordered rows without pagination
+    /// The rows are streamed to the client, so a big export does not fill the memory.
+    pub async fn run_multi_row_sql_and_export(&mut self) -> ResultResponse {
+        let response_format = self.response_format;
+        self.run_export_steps()
+            .await
+            .map_err(|err| response_format.error(err))
+    }
+
+    /// the steps return LibError, so the error can be rendered as html or json
+    async fn run_export_steps(&mut self) -> Result<actix_web::HttpResponse, LibError> {
+        use futures_util::StreamExt;
+        // region: 2. find out the filters and the export format from the parameters
+        self.prepare_filter_params()?;
+        let export_format = crate::csv_mod::ExportFormat::from_web_params(&self.web_params)?;
+        // endregion
+
+        // region: 3. cast the web data to call the function with correct data types
+        let sql_params = self.ref_to_function_params();
+        // endregion
+
+        // region: 4. start the sql statement and stream the rows
+        let query = format!(
+            "SELECT * FROM {} {} {};",
+            self.view_name.0, self.sql_where, self.sql_order_by
+        );
+        let client =
+            crate::deadpool_mod::get_postgres_client_from_pool(&self.app_state.db_pool).await?;
+        // the stream cannot borrow a transaction object, so the transaction is on the client
+        // it is read-only and it is rolled back when the stream is dropped
+        client
+            .batch_execute("BEGIN READ ONLY")
+            .await
+            .map_err(|err| crate::postgres_mod::sql_error_to_lib_error(err, "BEGIN", &[]))?;
+        let client = RollbackOnDrop(Some(client));
+        crate::postgres_mod::set_local_app_user_id(client.client(), self.app_user_id).await?;
+        let statement =
+            client.client().prepare(&query).await.map_err(|err| {
+                crate::postgres_mod::sql_error_to_lib_error(err, &query, &sql_params)
+            })?;
+        // the header is known from the statement, even if there are no rows
+        let column_names: Vec<Option<String>> = statement
+            .columns()
+            .iter()
+            .map(|x| Some(x.name().to_string()))
+            .collect();
+        let header_line = export_format.header_line(&column_names);
+        let row_stream = client
+            .client()
+            .query_raw(&statement, sql_params.iter().copied())
+            .await
+            .map_err(|err| crate::postgres_mod::sql_error_to_lib_error(err, &query, &sql_params))?;
+        let lines = row_stream.map(move |row| {
+            // the client is moved into the stream and returns to the pool after the last row
+            let _client = &client;
+            let row =
+                row.map_err(|err| crate::postgres_mod::sql_error_to_lib_error(err, &query, &[]))?;
+            let values = (0..row.len())
+                .map(|i| crate::postgres_type_mod::row_value_to_string(&row, i))
+                .collect::<Result<Vec<_>, LibError>>()?;
+            Ok::<_, LibError>(actix_web::web::Bytes::from(export_format.line(&values)))
+        });
+        let body = futures_util::stream::once(async move {
+            Ok::<_, LibError>(actix_web::web::Bytes::from(header_line))
+        })
+        .chain(lines);
+        // endregion
+
+        // region: 5. return a streaming response with no cache
+        use actix_web::http::header;
+        let file_name = format!("{}.{}", self.view_name.0, export_format.file_extension());
+        Ok(actix_web::HttpResponse::Ok()
+            .append_header((header::CONTENT_TYPE, export_format.content_type()))
+            .append_header(header::ContentDisposition {
+                disposition: header::DispositionType::Attachment,
+                parameters: vec![header::DispositionParam::Filename(file_name)],
+            })
+            .append_header(header::CacheControl(vec![header::CacheDirective::NoStore]))
+            .streaming(body))
         // endregion
     }
 
     /// prepares where clause and params inside struct field sql_where and sql_params
     /// filter: f_like_webpage
     /// if starts with f_like_ use the like operator
-    pub fn prepare_filter_params(&mut self) {
+    /// The param is cast to the data type of the field. The field name is the first word
+    /// in the where clause: "hit_count > {f_gt_hit_count}"
+    /// or the data type is declared after a colon: "length(webpage) > {f_gt_length:int4}"
+    /// Empty filters are ignored.
+    pub fn prepare_filter_params(&mut self) -> Result<(), LibError> {
         let mut placeholder = 1;
         let mut where_inter_word = "WHERE ";
 
@@ -131,12 +388,26 @@ impl<'a> ServerSideMultiRow<'a> {
             for m in RGX_01.captures_iter(single_line_of_where) {
                 // every group captured inside the match
                 for i in 1..m.len() {
-                    let param_name = m.get(i).unwrap().as_str();
+                    let param_spec = m.get(i).unwrap().as_str();
+                    let (param_name, declared_type) = match param_spec.split_once(':') {
+                        Some((param_name, declared_type)) => (param_name, Some(declared_type)),
+                        None => (param_spec, None),
+                    };
                     // dbg!(param_name);
-                    // check if param exists in web_params
-                    if let Some(param_from_web) =
-                        self.web_params.0.iter().find(|&x| x.0 == param_name)
+                    // check if param exists in web_params and is not empty
+                    if self
+                        .web_params
+                        .0
+                        .get(param_name)
+                        .filter(|x| !x.is_empty())
+                        .is_some()
                     {
+                        let field_type =
+                            self.filter_field_type(single_line_of_where, declared_type)?;
+                        let value = self
+                            .web_params
+                            .get_postgres_value_for_field(param_name, &field_type)?;
+
                         // the first inter_word is WHERE, later is AND
                         self.sql_where.push_str(where_inter_word);
                         if where_inter_word != " AND " {
@@ -146,76 +417,333 @@ impl<'a> ServerSideMultiRow<'a> {
                         let placeholder_str = format!("${placeholder}");
                         placeholder += 1;
 
-                        let param_name_placeholder = format!("{{{}}}", param_name);
+                        let param_name_placeholder = format!("{{{}}}", param_spec);
                         let where_clause =
                             single_line_of_where.replace(&param_name_placeholder, &placeholder_str);
                         self.sql_where.push_str(&where_clause);
-                        self.sql_params
-                            .push(PostgresValue::String(param_from_web.1.to_string()));
+                        self.sql_params.push(value);
                     }
                 }
             }
         }
 
         // region: order by
-        let view_field_type = self.app_state.sql_view_fields.get(&self.view_name).unwrap();
-
-        for (name, value) in self.web_params.0.iter() {
-            if !value.is_empty() {
-                if name == "f_order_by" {
-                    let field_name = FieldName(value.to_string());
-                    let _field_type = view_field_type.get(&field_name).unwrap();
-
-                    self.sql_order_by
-                        .push_str(&format!("ORDER BY {}", field_name.0));
-                } else if name == "f_order_by_direction" {
-                    let direction = value.to_string();
-                    if direction.to_lowercase() == "desc" {
-                        self.sql_order_by.push_str(" DESC ");
-                    }
-                }
-            }
-        }
+        self.prepare_order_by()?;
         // endregion: order by
 
         // dbg!(&self.sql_where);
         // dbg!(&self.sql_params);
         // dbg!(&self.sql_order_by);
+        Ok(())
+    }
+
+    /// prepares the ORDER BY clause inside struct field sql_order_by
+    /// from the web param f_order_by=webpage,-hit_count:nulls_last
+    /// Only the fields of the view are allowed and they are quoted as identifiers.
+    pub fn prepare_order_by(&mut self) -> Result<(), LibError> {
+        let sql_metadata = self.app_state.sql_metadata.get();
+        let view_field_type = sql_metadata
+            .view_fields
+            .get(&self.view_name)
+            .ok_or_else(|| LibError::SqlMetadataNotFound {
+                user_friendly: self.view_name.0.clone(),
+                developer_friendly: "the view does not exist in sql_view_fields".to_string(),
+                source_line_column: format!("{}:{}:{}", file!(), line!(), column!()),
+            })?;
+
+        let f_order_by = self
+            .web_params
+            .0
+            .get("f_order_by")
+            .cloned()
+            .unwrap_or_default();
+        for text in f_order_by
+            .split(',')
+            .map(str::trim)
+            .filter(|x| !x.is_empty())
+        {
+            let order_by_field = OrderByField::parse(text)
+                .filter(|x| view_field_type.contains_key(&x.field_name))
+                .ok_or_else(|| LibError::InvalidWebParam {
+                    user_friendly: format!("f_order_by is not valid: {text}"),
+                    developer_friendly: format!("{:?}", self.web_params.0),
+                    source_line_column: format!("{}:{}:{}", file!(), line!(), column!()),
+                })?;
+            self.order_by.push(order_by_field);
+        }
+
+        // LIMIT and OFFSET need a deterministic order, so the key field is always the last one
+        let mut fields: Vec<String> = self.order_by.iter().map(|x| x.to_sql()).collect();
+        if let Some(key_field) = OrderByField::parse(self.default_order_by)
+            .filter(|x| view_field_type.contains_key(&x.field_name))
+        {
+            if !self
+                .order_by
+                .iter()
+                .any(|x| x.field_name == key_field.field_name)
+            {
+                fields.push(key_field.to_sql());
+            }
+            // without f_order_by the sort indicator shows the default order
+            if self.order_by.is_empty() {
+                self.order_by.push(key_field);
+            }
+        }
+        if !fields.is_empty() {
+            self.sql_order_by = format!("ORDER BY {}", fields.join(", "));
+        }
+        Ok(())
+    }
+
+    /// the data type of the filter is declared in the where clause
+    /// or else it is the data type of the field in the view
+    fn filter_field_type(
+        &self,
+        single_line_of_where: &str,
+        declared_type: Option<&str>,
+    ) -> Result<PostgresFieldType, LibError> {
+        if let Some(declared_type) = declared_type {
+            use std::str::FromStr;
+            return PostgresFieldType::from_str(declared_type).map_err(|_err| {
+                LibError::SqlMetadataNotFound {
+                    user_friendly: declared_type.to_string(),
+                    developer_friendly: format!("unknown data type in: {single_line_of_where}"),
+                    source_line_column: format!("{}:{}:{}", file!(), line!(), column!()),
+                }
+            });
+        }
+        let field_name = FieldName(
+            single_line_of_where
+                .split_whitespace()
+                .next()
+                .unwrap_or_default()
+                .to_string(),
+        );
+        self.app_state
+            .sql_metadata
+            .get()
+            .view_fields
+            .get(&self.view_name)
+            .and_then(|view_field_type| view_field_type.get(&field_name))
+            .copied()
+            .ok_or_else(|| LibError::SqlMetadataNotFound {
+                user_friendly: field_name.0.clone(),
+                developer_friendly: format!(
+                    "the field does not exist in view {}: {single_line_of_where}",
+                    self.view_name.0
+                ),
+                source_line_column: format!("{}:{}:{}", file!(), line!(), column!()),
+            })
+    }
+
+    /// prepares page and page_size inside struct fields for LIMIT and OFFSET
+    /// page starts with 1, page_size is limited to MAX_PAGE_SIZE
+    pub fn prepare_pagination(&mut self) -> Result<(), LibError> {
+        self.page = self.web_params.get_positive_i64_or_default("page", 1)?;
+        self.page_size = self
+            .web_params
+            .get_positive_i64_or_default("page_size", DEFAULT_PAGE_SIZE)?
+            .min(MAX_PAGE_SIZE);
+        Ok(())
+    }
+
+    /// count of pages for all the filtered rows
+    /// there is always at least one page, even if it is empty
+    pub fn page_count(&self) -> i64 {
+        ((self.row_count + self.page_size - 1) / self.page_size).max(1)
+    }
+
+    /// relative url for the same view with the same web params, except the changed ones
+    /// the links in templates are relative to the scope
+    pub fn url_with_changed_params(&self, changed_params: &[(&str, String)]) -> String {
+        self.url_for_path_with_changed_params(&self.view_name.0, changed_params)
+    }
+
+    /// relative url for another route with the same web params, except the changed ones
+    pub fn url_for_path_with_changed_params(
+        &self,
+        path: &str,
+        changed_params: &[(&str, String)],
+    ) -> String {
+        let mut params: Vec<(&str, &str)> = self
+            .web_params
+            .0
+            .iter()
+            .filter(|(name, _)| !changed_params.iter().any(|x| x.0 == name.as_str()))
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+            .collect();
+        params.extend(
+            changed_params
+                .iter()
+                .map(|(name, value)| (*name, value.as_str())),
+        );
+        // HashMap has random order, but the same url must look always the same
+        params.sort();
+        let query = serde_urlencoded::to_string(params).unwrap_or_default();
+        format!("{}?{}", path, query)
+    }
+
+    /// replace the placeholders for the export links in the html template
+    /// {export_csv_url}, {export_tsv_url}, {export_excel_url}
+    /// the export uses the same filters and order, but it ignores the pagination
+    pub fn template_replace_export(&self, body: &str) -> String {
+        let path = format!("{}_export", self.view_name.0);
+        let mut body = body.to_string();
+        for format in ["csv", "tsv", "excel"] {
+            let url =
+                self.url_for_path_with_changed_params(&path, &[("format", format.to_string())]);
+            body = body.replace(
+                &format!("{{export_{format}_url}}"),
+                &html_escape::encode_double_quoted_attribute(&url),
+            );
+        }
+        body
+    }
+
+    /// replace the placeholders for the pager in the html template
+    /// {page}, {page_size}, {page_count}, {row_count},
+    /// {first_page_url}, {prev_page_url}, {next_page_url}, {last_page_url}
+    pub fn template_replace_pagination(&self, body: &str) -> String {
+        let page_count = self.page_count();
+        let url_for_page = |page: i64| self.url_with_changed_params(&[("page", page.to_string())]);
+        let placeholders = [
+            ("{page}", self.page.to_string()),
+            ("{page_size}", self.page_size.to_string()),
+            ("{page_count}", page_count.to_string()),
+            ("{row_count}", self.row_count.to_string()),
+            ("{first_page_url}", url_for_page(1)),
+            (
+                "{prev_page_url}",
+                url_for_page((self.page - 1).clamp(1, page_count)),
+            ),
+            (
+                "{next_page_url}",
+                url_for_page((self.page + 1).min(page_count)),
+            ),
+            ("{last_page_url}", url_for_page(page_count)),
+        ];
+        let mut body = body.to_string();
+        for (placeholder, value) in placeholders.iter() {
+            body = body.replace(
+                placeholder,
+                &html_escape::encode_double_quoted_attribute(value),
+            );
+        }
+        body
+    }
+
+    /// replace the placeholders for sortable column headers in the html template
+    /// {sort_url_webpage} is the url to order by this field first,
+    /// clicking again on the first field toggles the direction.
+    /// {sort_indicator_webpage} is ▲ or ▼ if the list is ordered by this field
+    pub fn template_replace_sorting(&self, body: &str) -> String {
+        let mut body = body.to_string();
+        let sql_metadata = self.app_state.sql_metadata.get();
+        let view_field_type = match sql_metadata.view_fields.get(&self.view_name) {
+            Some(view_field_type) => view_field_type,
+            None => return body,
+        };
+        for field_name in view_field_type.keys() {
+            let position = self
+                .order_by
+                .iter()
+                .position(|x| x.field_name == *field_name);
+            let clicked = OrderByField {
+                field_name: field_name.clone(),
+                descending: position == Some(0) && !self.order_by[0].descending,
+                nulls: position.and_then(|i| self.order_by[i].nulls),
+            };
+            // the clicked field is the first, the other fields keep their order
+            let mut new_order = vec![clicked.to_web_param()];
+            new_order.extend(
+                self.order_by
+                    .iter()
+                    .filter(|x| x.field_name != *field_name)
+                    .map(|x| x.to_web_param()),
+            );
+            let sort_url = self.url_with_changed_params(&[
+                ("f_order_by", new_order.join(",")),
+                ("page", "1".to_string()),
+            ]);
+            let sort_indicator = match position.map(|i| self.order_by[i].descending) {
+                Some(true) => "▼",
+                Some(false) => "▲",
+                None => "",
+            };
+            body = body
+                .replace(
+                    &format!("{{sort_url_{}}}", field_name.0),
+                    &html_escape::encode_double_quoted_attribute(&sort_url),
+                )
+                .replace(
+                    &format!("{{sort_indicator_{}}}", field_name.0),
+                    sort_indicator,
+                );
+        }
+        body
     }
 
     /// returns a reference to the values in the struct field sql_params
     /// this is the format expected by the postgres library
     pub fn ref_to_function_params(&self) -> Vec<&(dyn tokio_postgres::types::ToSql + Sync)> {
-        let mut sql_params: Vec<&(dyn tokio_postgres::types::ToSql + Sync)> = vec![];
+        self.sql_params.iter().map(|x| x.as_to_sql()).collect()
+    }
 
-        for x in self.sql_params.iter() {
-            match x {
-                PostgresValue::String(xx) => sql_params.push(xx),
-                PostgresValue::I32(xx) => sql_params.push(xx),
-            }
-        }
-        sql_params
+    /// run sql statement to count all rows with the same WHERE clause
+    /// it is needed to calculate the page_count
+    pub async fn run_sql_count_statement(
+        &self,
+        postgres_client: &impl tokio_postgres::GenericClient,
+        sql_params: &[&(dyn tokio_postgres::types::ToSql + Sync)],
+    ) -> Result<i64, LibError> {
+        let query = format!(
+            "SELECT count(*) FROM {} {};",
+            self.view_name.0, self.sql_where
+        );
+        let row_set = postgres_client
+            .query(&query, sql_params)
+            .await
+            .map_err(|err| crate::postgres_mod::sql_error_to_lib_error(err, &query, sql_params))?;
+        // count(*) returns always exactly one row of type bigint
+        Ok(row_set[0].get(0))
     }
 
-    /// run sql multi row statement
+    /// run sql multi row statement only for the rows in the current page
     /// void function also returns a Row with the data type Void
     pub async fn run_sql_multi_row_statement(
         &self,
+        postgres_client: &impl tokio_postgres::GenericClient,
         sql_params: Vec<&(dyn tokio_postgres::types::ToSql + Sync)>,
-    ) -> Vec<tokio_postgres::Row> {
-        let postgres_client =
-            crate::deadpool_mod::get_postgres_client_from_pool(&self.app_state.db_pool)
-                .await
-                .unwrap();
-
+    ) -> Result<Vec<tokio_postgres::Row>, LibError> {
+        // page and page_size are validated integers and it is safe to use them in the sql text
+        let offset = (self.page - 1).saturating_mul(self.page_size);
         let query = format!(
-            "SELECT * FROM {} {} {};",
-            self.view_name.0, self.sql_where, self.sql_order_by
+            "SELECT * FROM {} {} {} LIMIT {} OFFSET {};",
+            self.view_name.0, self.sql_where, self.sql_order_by, self.page_size, offset
         );
         // dbg!(&query);
-        // TODO: convert sql errors in a single place
-        let row_set = postgres_client.query(&query, &sql_params).await.unwrap();
+        postgres_client
+            .query(&query, &sql_params)
+            .await
+            .map_err(|err| crate::postgres_mod::sql_error_to_lib_error(err, &query, &sql_params))
+    }
+}
+
+/// the client with the open read-only transaction of the export stream
+/// The transaction must not stay open, when the client returns to the pool.
+struct RollbackOnDrop(Option<deadpool_postgres::Object>);
+
+impl RollbackOnDrop {
+    fn client(&self) -> &tokio_postgres::Client {
+        // the option is None only inside drop()
+        self.0.as_ref().unwrap()
+    }
+}
 
-        row_set
+impl Drop for RollbackOnDrop {
+    fn drop(&mut self) {
+        if let Some(postgres_client) = self.0.take() {
+            crate::deadpool_mod::spawn_rollback(postgres_client);
+        }
     }
 }
@@ -4,24 +4,51 @@
 // for single row: (new, insert, show, edit, update, delete):
 // 1. parse web data: strings coming from the browser in path, query and form
 // 2. find out the parameters of an sql function with data types
+//    The overloaded function is resolved with the names and values of the web params.
+//    The missing web params are omitted in the call, if the input params have defaults.
 // 3. cast the web data to call the function with correct data types
 // 4. retrieve sql data as single row or void
 // 5. read html template (presentation) from disk or cache
 // 6. mix presentation and data, because this is server-side rendering
 // 7. return a response with no cache (because data in database can change fast)
+// For json requests the steps 5. and 6. are replaced with the serialization of the row to json.
+// Optionally the request opens a transaction and runs more sql functions on the same client.
+// The transaction is committed after step 4. or rolled back on the first error.
+// Every sql function runs inside a transaction with the local setting app.user_id
+// for the row-level security policies in postgres.
+// The calls of data modifying functions are written in the audit log after the request.
 
-use crate::actix_mod::{DataAppState, ResultResponse, WebForm, WebQuery};
-use crate::postgres_mod::FunctionName;
+use crate::actix_mod::WebRequest;
+use crate::actix_mod::{DataAppState, ResponseFormat, ResultResponse, WebForm, WebQuery};
+use crate::audit_mod::AuditCall;
+use crate::error_mod::LibError;
+use crate::postgres_mod::{FunctionName, FunctionOverload};
 use crate::postgres_type_mod::PostgresValue;
 use crate::web_params_mod::WebParams;
 
 /// the main ServerSideSingleRow object (struct with implementation)
 pub struct ServerSideSingleRow<'a> {
     app_state: &'a DataAppState,
+    req: &'a WebRequest,
     scope: &'a str,
+    response_format: ResponseFormat,
+    /// the token for the placeholder {csrf_token} in forms that modify data
+    csrf_token: String,
     function_name: FunctionName,
     web_params: WebParams,
+    /// the overload of the function that matches the web params
+    function_overload: Option<FunctionOverload>,
     sql_params: Vec<PostgresValue>,
+    /// the client with the open transaction, None in autocommit mode
+    transaction_client: Option<deadpool_postgres::Object>,
+    /// the logged in user for the row-level security policies
+    app_user_id: Option<i32>,
+    /// the calls of data modifying functions for the audit log
+    audit_calls: Vec<AuditCall>,
+    /// the transaction was rolled back, so its calls are rolled_back in the audit log
+    is_rolled_back: bool,
+    /// the request is POST with the valid csrf token, so it can run data modifying functions
+    is_csrf_verified: bool,
 }
 
 impl<'a> ServerSideSingleRow<'a> {
@@ -30,30 +57,82 @@ impl<'a> ServerSideSingleRow<'a> {
     #[track_caller]
     pub fn new(
         app_state: &'a DataAppState,
+        req: &'a WebRequest,
         scope: &'a str,
-        function_name: &'static str,
+        function_name: &str,
         query: &'a WebQuery,
         form: &'a Option<WebForm>,
-    ) -> ServerSideSingleRow<'a> {
+    ) -> Result<ServerSideSingleRow<'a>, actix_web::Error> {
         println!("{} {}", crate::error_mod::time_epoch(), function_name);
         // region: 1. parse web data: strings coming from the browser in path, query and form
-        let web_params = WebParams::from_actix(query, form);
+        // the data modifying function needs POST with the csrf token
+        let is_data_modifying = FunctionName(function_name.to_string()).is_data_modifying();
+        let web_params = WebParams::from_actix(req, query, form, is_data_modifying)
+            .map_err(|err| ResponseFormat::from_request(req).error(err))?;
         // endregion
 
+        // the role of the user must allow this function
+        crate::auth_mod::check_permission(app_state, req, function_name)
+            .map_err(|err| ResponseFormat::from_request(req).error(err))?;
+
+        let mut sssr =
+            ServerSideSingleRow::from_web_params(app_state, req, scope, function_name, web_params);
+        sssr.is_csrf_verified = is_data_modifying;
+        Ok(sssr)
+    }
+
+    /// constructor with web params that do not come from the query or form
+    /// for example from a row of the imported csv file
+    /// The csrf token is not verified, so run_sql_function() cannot run data modifying functions.
+    pub fn from_web_params(
+        app_state: &'a DataAppState,
+        req: &'a WebRequest,
+        scope: &'a str,
+        function_name: &str,
+        web_params: WebParams,
+    ) -> ServerSideSingleRow<'a> {
         ServerSideSingleRow {
             app_state,
+            req,
             scope,
+            response_format: ResponseFormat::from_request(req),
+            csrf_token: crate::csrf_mod::csrf_token(req),
             function_name: FunctionName(function_name.to_string()),
             web_params,
+            function_overload: None,
             sql_params: vec![],
+            transaction_client: None,
+            app_user_id: crate::auth_mod::app_user_id(req),
+            audit_calls: vec![],
+            is_rolled_back: false,
+            is_csrf_verified: false,
         }
     }
 
     /// typical steps for a web app function for single Row sql function (or void function)
     /// These steps can be called separately if some customization is needed
     pub async fn run_single_row_sql_and_process_html(&mut self) -> ResultResponse {
+        let response_format = self.response_format;
+        let result = match self.run_single_row_steps().await {
+            Ok(response) => Ok(response),
+            Err(err) => Err(self.rollback_transaction(err).await),
+        };
+        // the calls are taken, so the Drop does not write them again
+        let audit_calls = std::mem::take(&mut self.audit_calls);
+        crate::audit_mod::insert_audit_log(
+            self.app_state,
+            self.req,
+            &audit_calls,
+            self.is_rolled_back,
+        )
+        .await;
+        result.map_err(|err| response_format.error(err))
+    }
+
+    /// the steps return LibError, so the error can be rendered as html or json
+    async fn run_single_row_steps(&mut self) -> Result<actix_web::HttpResponse, LibError> {
         // region: 2. find out the parameters of an sql function with data types
-        self.prepare_function_params();
+        self.prepare_function_params()?;
         // endregion
 
         // region: 3. cast the web data to call the function with correct data types
@@ -62,98 +141,427 @@ impl<'a> ServerSideSingleRow<'a> {
 
         // region: 4. retrieve sql data as single row or void
         let single_row = self.run_sql_single_row_function(sql_params).await;
+        let audit_call = self.audit_call(
+            &self.function_name,
+            self.function_overload.as_ref(),
+            &self.sql_params,
+            &single_row,
+        );
+        self.audit_calls.extend(audit_call);
+        let single_row = single_row?;
+        self.commit_transaction().await?;
         // endregion
 
+        if self.response_format == ResponseFormat::Json {
+            let value = crate::postgres_type_mod::row_to_json(&single_row)?;
+            return Ok(crate::actix_mod::return_json_response_no_cache(value));
+        }
+
         // region: 5. read html template (presentation) from disk or cache
-        let body = crate::html_templating_mod::read_template(self.scope, &self.function_name.0);
+        let template = self
+            .app_state
+            .html_templates
+            .get(self.scope, &self.function_name.0)?;
         // endregion
 
         // region: 6. mix presentation and data, because this is server-side rendering
-        let body =
-            crate::html_templating_mod::template_replace_fields_from_single_row(&body, single_row);
+        let body = crate::html_templating_mod::template_replace_fields_from_single_row(
+            &template.text,
+            single_row,
+            &self.app_state.html_null_marker,
+        )?
+        .replace("{csrf_token}", &self.csrf_token);
         // endregion
 
         // region: 7. return a response with no cache (because data in database can change fast)
-        crate::actix_mod::return_response_no_cache(body)
+        Ok(crate::actix_mod::return_response_no_cache(body))
         // endregion
     }
 
     /// prepares input params for sql function inside struct field sql_params
     /// the param order is important
-    pub fn prepare_function_params(&mut self) {
-        let name_type = self
-            .app_state
-            .sql_function_input_params
-            .get(&self.function_name)
-            .unwrap();
+    pub fn prepare_function_params(&mut self) -> Result<(), LibError> {
+        let (function_overload, sql_params) =
+            self.function_params(&self.function_name, &self.web_params)?;
+        self.function_overload = Some(function_overload);
+        self.sql_params = sql_params;
+        Ok(())
+    }
 
-        let param_name_order = self
+    /// resolve the overload of the function and its input params from the web params
+    /// The overload is resolved like in postgres: all the params without defaults must be found and valid.
+    /// If more overloads match, the one with the most params is used.
+    fn function_params(
+        &self,
+        function_name: &FunctionName,
+        web_params: &WebParams,
+    ) -> Result<(FunctionOverload, Vec<PostgresValue>), LibError> {
+        let mut function_overloads = self
             .app_state
-            .sql_function_input_params_order
-            .get(&self.function_name)
-            .unwrap();
+            .sql_metadata
+            .get()
+            .function_overloads(function_name)?;
+        // without overloads the error tells exactly which web param is missing or not valid
+        if function_overloads.len() == 1 {
+            return self.overload_params(function_overloads.remove(0), web_params);
+        }
+        let mut matching: Vec<_> = function_overloads
+            .into_iter()
+            .filter_map(|function_overload| {
+                self.overload_params(function_overload, web_params).ok()
+            })
+            .collect();
+        let max_params = matching.iter().map(|x| x.1.len()).max().unwrap_or_default();
+        matching.retain(|x| x.1.len() == max_params);
+        if matching.len() == 1 {
+            return Ok(matching.remove(0));
+        }
+        let signatures: Vec<_> = matching
+            .iter()
+            .map(|x| x.0.function_key.signature.as_str())
+            .collect();
+        Err(LibError::InvalidWebParam {
+            user_friendly: match matching.len() {
+                0 => format!("no overload of {} matches the params", function_name.0),
+                _ => format!("more overloads of {} match the params", function_name.0),
+            },
+            developer_friendly: format!("{signatures:?} {:?}", web_params),
+            source_line_column: format!("{}:{}:{}", file!(), line!(), column!()),
+        })
+    }
 
+    /// input params for the overload of the sql function from the web params
+    /// The missing web param is omitted, if the input param has a default.
+    /// Returns the overload with only the params for this call.
+    /// the param order is important
+    fn overload_params(
+        &self,
+        function_overload: FunctionOverload,
+        web_params: &WebParams,
+    ) -> Result<(FunctionOverload, Vec<PostgresValue>), LibError> {
+        let mut params = vec![];
+        let mut sql_params = vec![];
         // params must be in the correct order
-        for param_name in param_name_order.iter() {
-            let name = param_name
-                .0
-                .trim_start_matches("_")
-                .trim_start_matches("in_");
+        for param in function_overload.params.into_iter() {
+            let name = param.param_name.web_param_name();
             // dbg!(&name);
-
-            let sql_type = name_type.get(&param_name).unwrap();
-
-            // dbg!(sql_type.as_ref());
-            match sql_type.as_ref() {
-                "character" => {
-                    self.sql_params.push(PostgresValue::String(
-                        self.web_params.get_str(name).unwrap().to_string(),
-                    ));
-                }
-                "integer" => {
-                    self.sql_params
-                        .push(PostgresValue::I32(self.web_params.get_i32(name).unwrap()));
-                }
-                _ => panic!("sql_type is unknown: {:?}", sql_type),
+            if param.has_default && !web_params.0.contains_key(name) {
+                continue;
             }
+            // empty string can be NULL, depending on the sql_type
+            let value = web_params.get_postgres_value_for_input(name, &param.input_type)?;
+            sql_params.push(value);
+            params.push(param);
         }
 
-        // dbg!(&self.sql_params);
+        // dbg!(&sql_params);
+        let function_overload = FunctionOverload {
+            function_key: function_overload.function_key,
+            params,
+        };
+        Ok((function_overload, sql_params))
+    }
+
+    /// the input params of the main function after prepare_function_params()
+    pub fn sql_params(&self) -> &[PostgresValue] {
+        &self.sql_params
+    }
+
+    /// the overload of the main function after prepare_function_params()
+    pub fn function_overload(&self) -> Result<&FunctionOverload, LibError> {
+        self.function_overload
+            .as_ref()
+            .ok_or_else(|| LibError::SqlMetadataNotFound {
+                user_friendly: self.function_name.0.clone(),
+                developer_friendly: "call prepare_function_params() first".to_string(),
+                source_line_column: format!("{}:{}:{}", file!(), line!(), column!()),
+            })
     }
 
     /// returns a reference to the values in the struct field sql_params
     /// this is the format expected by the postgres library
     pub fn ref_to_function_params(&self) -> Vec<&(dyn tokio_postgres::types::ToSql + Sync)> {
-        let mut sql_params: Vec<&(dyn tokio_postgres::types::ToSql + Sync)> = vec![];
-
-        for x in self.sql_params.iter() {
-            match x {
-                PostgresValue::String(xx) => sql_params.push(xx),
-                PostgresValue::I32(xx) => sql_params.push(xx),
-            }
-        }
-        sql_params
+        self.sql_params.iter().map(|x| x.as_to_sql()).collect()
     }
 
     /// run sql single row function or void function
     /// void function also returns a Row with the data type Void
+    /// if the function returns no row, it is the RowNotFound error
+    /// If the transaction is open, the function runs inside the transaction.
     pub async fn run_sql_single_row_function(
         &self,
         sql_params: Vec<&(dyn tokio_postgres::types::ToSql + Sync)>,
-    ) -> tokio_postgres::Row {
-        let postgres_client =
-            crate::deadpool_mod::get_postgres_client_from_pool(&self.app_state.db_pool)
+    ) -> Result<tokio_postgres::Row, LibError> {
+        let function_overload = self.function_overload()?;
+        match &self.transaction_client {
+            Some(postgres_client) => {
+                self.run_sql_single_row_function_on_client(
+                    &***postgres_client,
+                    function_overload,
+                    sql_params,
+                )
                 .await
-                .unwrap();
+            }
+            None => {
+                self.run_sql_single_row_function_in_new_transaction(function_overload, sql_params)
+                    .await
+            }
+        }
+    }
+
+    /// in autocommit mode the function runs in its own short transaction,
+    /// because the setting app.user_id is local to the transaction
+    async fn run_sql_single_row_function_in_new_transaction(
+        &self,
+        function_overload: &FunctionOverload,
+        sql_params: Vec<&(dyn tokio_postgres::types::ToSql + Sync)>,
+    ) -> Result<tokio_postgres::Row, LibError> {
+        let map_err = |err| crate::postgres_mod::sql_error_to_lib_error(err, "transaction", &[]);
+        let mut postgres_client =
+            crate::deadpool_mod::get_postgres_client_from_pool(&self.app_state.db_pool).await?;
+        // on error the dropped transaction is rolled back
+        let transaction = postgres_client.transaction().await.map_err(map_err)?;
+        crate::postgres_mod::set_local_app_user_id(&*transaction, self.app_user_id).await?;
+        let row = self
+            .run_sql_single_row_function_on_client(&*transaction, function_overload, sql_params)
+            .await?;
+        transaction.commit().await.map_err(map_err)?;
+        Ok(row)
+    }
 
-        let placeholders = crate::postgres_mod::prepare_placeholders_for_sql_params(&sql_params);
-        let query = format!("SELECT * from {}({});", self.function_name.0, placeholders);
-        // TODO: convert sql errors in a single place
+    /// run sql single row function on the client or inside a transaction
+    /// The function is called with the schema-qualified name and the params cast to its types.
+    pub async fn run_sql_single_row_function_on_client(
+        &self,
+        postgres_client: &impl tokio_postgres::GenericClient,
+        function_overload: &FunctionOverload,
+        sql_params: Vec<&(dyn tokio_postgres::types::ToSql + Sync)>,
+    ) -> Result<tokio_postgres::Row, LibError> {
+        let query = function_overload.select_query();
         let row = postgres_client
-            .query_one(&query, &sql_params)
+            .query_opt(&query, &sql_params)
             .await
-            .expect(&format!("{}, {:?}", query, sql_params));
+            .map_err(|err| crate::postgres_mod::sql_error_to_lib_error(err, &query, &sql_params))?;
+
+        row.ok_or_else(|| LibError::RowNotFound {
+            user_friendly: function_overload.function_key.function_name.clone(),
+            developer_friendly: format!("{} {:?}", query, sql_params),
+            source_line_column: format!("{}:{}:{}", file!(), line!(), column!()),
+        })
+    }
 
-        row
+    /// open a transaction for the whole request
+    /// All the sql functions of this object run on the same client until commit or rollback.
+    /// run_single_row_sql_and_process_html() commits after the main function
+    /// or rolls back on the first error.
+    /// The errors are already rendered as html or json and the transaction is rolled back:
+    /// sssr.begin_transaction().await?;
+    /// sssr.run_sql_function("webpage_hits_insert").await?;
+    /// sssr.run_single_row_sql_and_process_html().await
+    pub async fn begin_transaction(&mut self) -> Result<(), actix_web::Error> {
+        let result = self.try_begin_transaction().await;
+        self.or_rollback(result).await
+    }
+
+    async fn try_begin_transaction(&mut self) -> Result<(), LibError> {
+        // a second BEGIN would be only a warning in postgres and the first commit ends both
+        if self.transaction_client.is_some() {
+            return Err(LibError::Transaction {
+                user_friendly: self.function_name.0.clone(),
+                developer_friendly: "the transaction is already open".to_string(),
+                source_line_column: format!("{}:{}:{}", file!(), line!(), column!()),
+            });
+        }
+        let postgres_client =
+            crate::deadpool_mod::get_postgres_client_from_pool(&self.app_state.db_pool).await?;
+        postgres_client
+            .batch_execute("BEGIN")
+            .await
+            .map_err(|err| crate::postgres_mod::sql_error_to_lib_error(err, "BEGIN", &[]))?;
+        // the client is stored first, so the Drop rolls back also if set_config fails
+        let postgres_client = self.transaction_client.insert(postgres_client);
+        crate::postgres_mod::set_local_app_user_id(&***postgres_client, self.app_user_id).await?;
+        Ok(())
+    }
+
+    /// run another sql function with the web params of this request
+    /// If the transaction is open, the function runs inside the transaction.
+    pub async fn run_sql_function(
+        &mut self,
+        function_name: &str,
+    ) -> Result<tokio_postgres::Row, actix_web::Error> {
+        let (single_row, audit_call) = self
+            .run_sql_function_and_audit_call(function_name, &self.web_params)
+            .await;
+        self.record_audit_call(audit_call).await;
+        self.or_rollback(single_row).await
+    }
+
+    /// run another sql function with other web params than the params of this request
+    /// for example the parent is inserted with the web params and every child with its own params:
+    /// sssr.run_sql_function_with_params("invoice_line_insert", &line_params).await?;
+    pub async fn run_sql_function_with_params(
+        &mut self,
+        function_name: &str,
+        web_params: &WebParams,
+    ) -> Result<tokio_postgres::Row, actix_web::Error> {
+        let (single_row, audit_call) = self
+            .run_sql_function_and_audit_call(function_name, web_params)
+            .await;
+        self.record_audit_call(audit_call).await;
+        self.or_rollback(single_row).await
+    }
+
+    /// the error rolls back the transaction and is rendered as html or json,
+    /// so the web handler can return it with ?
+    async fn or_rollback<T>(&mut self, result: Result<T, LibError>) -> Result<T, actix_web::Error> {
+        match result {
+            Ok(value) => Ok(value),
+            Err(err) => {
+                let err = self.rollback_transaction(err).await;
+                Err(self.response_format.error(err))
+            }
+        }
+    }
+
+    /// the audit call is returned, because the web params can be borrowed from self
+    async fn run_sql_function_and_audit_call(
+        &self,
+        function_name: &str,
+        web_params: &WebParams,
+    ) -> (Result<tokio_postgres::Row, LibError>, Option<AuditCall>) {
+        // the role of the user must allow also this function
+        if let Err(err) = crate::auth_mod::check_permission(self.app_state, self.req, function_name)
+        {
+            return (Err(err), None);
+        }
+        let function_name = FunctionName(function_name.to_string());
+        // a GET request for show or new must not modify data
+        if function_name.is_data_modifying() && !self.is_csrf_verified {
+            return (
+                Err(LibError::CsrfTokenInvalid {
+                    user_friendly: function_name.0,
+                    developer_friendly: format!(
+                        "the request for {} is not verified with POST and the csrf token",
+                        self.function_name.0
+                    ),
+                    source_line_column: format!("{}:{}:{}", file!(), line!(), column!()),
+                }),
+                None,
+            );
+        }
+        let (function_overload, postgres_values) =
+            match self.function_params(&function_name, web_params) {
+                Ok(x) => x,
+                Err(err) => return (Err(err), None),
+            };
+        let sql_params: Vec<_> = postgres_values.iter().map(|x| x.as_to_sql()).collect();
+        let single_row = match &self.transaction_client {
+            Some(postgres_client) => {
+                self.run_sql_single_row_function_on_client(
+                    &***postgres_client,
+                    &function_overload,
+                    sql_params,
+                )
+                .await
+            }
+            None => {
+                self.run_sql_single_row_function_in_new_transaction(&function_overload, sql_params)
+                    .await
+            }
+        };
+        let audit_call = self.audit_call(
+            &function_name,
+            Some(&function_overload),
+            &postgres_values,
+            &single_row,
+        );
+        (single_row, audit_call)
+    }
+
+    /// in autocommit mode the call is already committed and is written in the audit log immediately
+    /// The call inside the transaction waits for the commit or rollback.
+    async fn record_audit_call(&mut self, audit_call: Option<AuditCall>) {
+        let Some(audit_call) = audit_call else {
+            return;
+        };
+        if audit_call.in_transaction {
+            self.audit_calls.push(audit_call);
+        } else {
+            crate::audit_mod::insert_audit_log(self.app_state, self.req, &[audit_call], false)
+                .await;
+        }
+    }
+
+    /// only the data modifying functions are recorded in the audit log
+    fn audit_call(
+        &self,
+        function_name: &FunctionName,
+        function_overload: Option<&FunctionOverload>,
+        sql_params: &[PostgresValue],
+        result: &Result<tokio_postgres::Row, LibError>,
+    ) -> Option<AuditCall> {
+        function_name.is_data_modifying().then(|| {
+            AuditCall::new(
+                function_name,
+                function_overload,
+                sql_params,
+                result.as_ref().map(|_| ()),
+                self.transaction_client.is_some(),
+            )
+        })
+    }
+
+    /// commit the transaction, if it is open
+    /// The failed COMMIT ends the transaction with a rollback.
+    pub async fn commit_transaction(&mut self) -> Result<(), LibError> {
+        if let Some(postgres_client) = self.transaction_client.take() {
+            if let Err(err) = postgres_client.batch_execute("COMMIT").await {
+                self.is_rolled_back = true;
+                return Err(crate::postgres_mod::sql_error_to_lib_error(
+                    err,
+                    "COMMIT",
+                    &[],
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// roll back the transaction, if it is open
+    /// the reason for the rollback is returned as the error of the request
+    /// If the ROLLBACK fails, the failure is only logged.
+    pub async fn rollback_transaction(&mut self, reason: LibError) -> LibError {
+        if let Some(postgres_client) = self.transaction_client.take() {
+            log::warn!("Transaction rolled back: {reason}");
+            self.is_rolled_back = true;
+            if let Err(err) = postgres_client.batch_execute("ROLLBACK").await {
+                log::error!("Rollback failed: {err}");
+                crate::deadpool_mod::discard_client(postgres_client);
+            }
+        }
+        reason
+    }
+}
+
+impl Drop for ServerSideSingleRow<'_> {
+    /// The transaction must not stay open, when the client returns to the pool.
+    /// It happens if the request returns early with an error.
+    /// The calls waiting for the end of the transaction are still written in the audit log.
+    fn drop(&mut self) {
+        let audit_calls = std::mem::take(&mut self.audit_calls);
+        if !audit_calls.is_empty() {
+            // the open transaction is rolled back here
+            let is_rolled_back = self.is_rolled_back || self.transaction_client.is_some();
+            let app_state = self.app_state.clone();
+            let req = self.req.clone();
+            actix_web::rt::spawn(async move {
+                crate::audit_mod::insert_audit_log(&app_state, &req, &audit_calls, is_rolled_back)
+                    .await;
+            });
+        }
+        if let Some(postgres_client) = self.transaction_client.take() {
+            crate::deadpool_mod::spawn_rollback(postgres_client);
+        }
     }
 }
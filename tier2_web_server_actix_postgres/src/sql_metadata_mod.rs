@@ -0,0 +1,247 @@
+//! sql_metadata_mod.rs
+
+// The metadata of sql functions, views and role permissions is cached in AppState.
+// 1. on start the cache is loaded once
+// 2. the admin can reload it with POST /webpage_hits_admin/sql_metadata/sql_metadata_refresh
+// 3. the event trigger in postgres sends NOTIFY sql_metadata_changed after DDL commands
+//    and the listener reloads the cache
+// The requests use a snapshot Arc<SqlMetadata>. The reload replaces the Arc and never blocks them.
+// The crud_scopes routes are discovered on start.
+// A new function or view needs a restart for the new route.
+
+use std::sync::{Arc, RwLock};
+
+use futures_util::StreamExt;
+
+use crate::actix_mod::{
+    DataAppState, ResponseFormat, ResultResponse, WebForm, WebQuery, WebRequest,
+};
+use crate::config_mod::PgConfig;
+use crate::error_mod::LibError;
+use crate::postgres_mod::{
+    FunctionName, FunctionOverload, SqlFunctionInputParams, SqlFunctionOverloads,
+    SqlRolePermissions, SqlViewFields,
+};
+use crate::web_params_mod::WebParams;
+
+/// the channel for LISTEN and NOTIFY, the same name is in the sql event trigger
+const NOTIFY_CHANNEL: &str = "sql_metadata_changed";
+/// a script with many DDL commands sends many notifications, one reload is enough
+const NOTIFY_DEBOUNCE: std::time::Duration = std::time::Duration::from_millis(500);
+/// wait before reconnecting the listener
+const LISTEN_RECONNECT: std::time::Duration = std::time::Duration::from_secs(10);
+/// after the refresh in the browser, if there is no valid next url
+const DEFAULT_NEXT_URL: &str = "/webpage_hits_admin/webpage_hits/webpage_hits_list";
+
+/// all the metadata is loaded together, so the overloads and the function params always match
+pub struct SqlMetadata {
+    pub function_input_params: SqlFunctionInputParams,
+    pub function_overloads: SqlFunctionOverloads,
+    pub view_fields: SqlViewFields,
+    /// the roles can call only the allowed functions and views
+    pub role_permissions: SqlRolePermissions,
+}
+
+impl SqlMetadata {
+    /// read all the metadata from the database
+    pub async fn load(db_pool: &deadpool_postgres::Pool) -> Result<SqlMetadata, LibError> {
+        // all the input parameters for sql functions.
+        // So I can parse string params to a correct rust data type.
+        let (function_input_params, function_overloads) =
+            crate::postgres_mod::get_for_cache_all_function_input_params(db_pool).await?;
+        // I need the view fields and types to construct the WHERE clause
+        let view_fields = crate::postgres_mod::get_for_cache_all_view_fields(db_pool).await?;
+        // the permissions of the roles are checked before every call of functions and views
+        let role_permissions =
+            crate::postgres_mod::get_for_cache_all_role_permissions(db_pool).await?;
+        Ok(SqlMetadata {
+            function_input_params,
+            function_overloads,
+            view_fields,
+            role_permissions,
+        })
+    }
+
+    /// all the overloads of the function with the input params
+    /// The function_name can be qualified with the schema: schema_name.function_name
+    pub fn function_overloads(
+        &self,
+        function_name: &FunctionName,
+    ) -> Result<Vec<FunctionOverload>, LibError> {
+        let function_not_found = || LibError::SqlMetadataNotFound {
+            user_friendly: function_name.0.clone(),
+            developer_friendly: "the function does not exist in sql_function_input_params"
+                .to_string(),
+            source_line_column: format!("{}:{}:{}", file!(), line!(), column!()),
+        };
+        self.function_overloads
+            .get(function_name)
+            .ok_or_else(function_not_found)?
+            .iter()
+            .map(|function_key| {
+                let params = self
+                    .function_input_params
+                    .get(function_key)
+                    .ok_or_else(function_not_found)?;
+                Ok(FunctionOverload {
+                    function_key: function_key.clone(),
+                    params: params.clone(),
+                })
+            })
+            .collect()
+    }
+}
+
+/// the cache can be replaced while the server is running
+pub struct SqlMetadataCache(RwLock<Arc<SqlMetadata>>);
+
+impl SqlMetadataCache {
+    pub fn new(sql_metadata: SqlMetadata) -> SqlMetadataCache {
+        SqlMetadataCache(RwLock::new(Arc::new(sql_metadata)))
+    }
+
+    /// the snapshot of the metadata, the lock is released immediately
+    pub fn get(&self) -> Arc<SqlMetadata> {
+        // the lock is never held while panicking, but the data is still valid
+        self.0.read().unwrap_or_else(|err| err.into_inner()).clone()
+    }
+
+    /// load the metadata from the database and replace the cache
+    /// On error the old metadata stays in the cache.
+    pub async fn refresh(&self, db_pool: &deadpool_postgres::Pool) -> Result<(), LibError> {
+        let sql_metadata = SqlMetadata::load(db_pool).await?;
+        log::info!(
+            "Sql metadata refreshed: {} functions, {} views, {} roles",
+            sql_metadata.function_input_params.len(),
+            sql_metadata.view_fields.len(),
+            sql_metadata.role_permissions.len()
+        );
+        *self.0.write().unwrap_or_else(|err| err.into_inner()) = Arc::new(sql_metadata);
+        Ok(())
+    }
+}
+
+/// scoped actix routing near the implementation code
+/// scope is already "/webpage_hits_admin/sql_metadata"
+pub fn config_route_sql_metadata(cfg: &mut actix_web::web::ServiceConfig) {
+    use actix_web::web::{post, resource};
+    cfg.service(resource("/sql_metadata_refresh").route(post().to(sql_metadata_refresh)));
+}
+
+/// reload the metadata after `create or replace function` without restarting the server
+/// The browser is redirected to the next url, the json client gets the count of objects.
+pub async fn sql_metadata_refresh(
+    app_state: DataAppState,
+    req: WebRequest,
+    query: WebQuery,
+    form: Option<WebForm>,
+) -> ResultResponse {
+    let response_format = ResponseFormat::from_request(&req);
+    let web_params = WebParams::from_actix(&req, &query, &form, true)
+        .map_err(|err| response_format.error(err))?;
+    crate::auth_mod::check_permission(&app_state, &req, "sql_metadata_refresh")
+        .map_err(|err| response_format.error(err))?;
+    app_state
+        .sql_metadata
+        .refresh(&app_state.db_pool)
+        .await
+        .map_err(|err| response_format.error(err))?;
+
+    match response_format {
+        ResponseFormat::Json => {
+            let sql_metadata = app_state.sql_metadata.get();
+            Ok(crate::actix_mod::return_json_response_no_cache(
+                serde_json::json!({
+                    "functions": sql_metadata.function_input_params.len(),
+                    "views": sql_metadata.view_fields.len(),
+                    "roles": sql_metadata.role_permissions.len(),
+                }),
+            ))
+        }
+        ResponseFormat::Html => {
+            let next = web_params
+                .0
+                .get("next")
+                .map(|x| x.as_str())
+                .filter(|x| crate::auth_mod::is_local_url(x))
+                .unwrap_or(DEFAULT_NEXT_URL);
+            Ok(actix_web::HttpResponse::SeeOther()
+                .append_header((actix_web::http::header::LOCATION, next))
+                .finish())
+        }
+    }
+}
+
+/// LISTEN on a dedicated connection outside of the pool and reload the cache on every NOTIFY
+/// The listener reconnects forever, if the connection is lost.
+pub fn spawn_sql_metadata_listener(app_state: DataAppState, config: &PgConfig) {
+    let config = config.clone();
+    actix_web::rt::spawn(async move {
+        loop {
+            let result = match config.connection.get_ssl_mode() {
+                tokio_postgres::config::SslMode::Disable => {
+                    listen(&app_state, &config.connection, tokio_postgres::NoTls).await
+                }
+                _ => {
+                    let tls = crate::deadpool_mod::rustls_connector(config.ssl_root_cert.as_ref());
+                    listen(&app_state, &config.connection, tls).await
+                }
+            };
+            match result {
+                Ok(()) => log::error!("The listener for {NOTIFY_CHANNEL} is disconnected"),
+                Err(err) => log::error!("The listener for {NOTIFY_CHANNEL} failed: {err}"),
+            }
+            actix_web::rt::time::sleep(LISTEN_RECONNECT).await;
+        }
+    });
+}
+
+/// returns when the connection is closed
+async fn listen<T>(
+    app_state: &DataAppState,
+    connection_config: &tokio_postgres::Config,
+    tls: T,
+) -> Result<(), tokio_postgres::Error>
+where
+    T: tokio_postgres::tls::MakeTlsConnect<tokio_postgres::Socket>,
+    T::Stream: 'static,
+{
+    let (client, mut connection) = connection_config.connect(tls).await?;
+    // the connection must be polled all the time to receive the notifications
+    let (sender, mut receiver) = futures_channel::mpsc::unbounded();
+    let connection_task = actix_web::rt::spawn(async move {
+        let mut messages = futures_util::stream::poll_fn(move |cx| connection.poll_message(cx));
+        while let Some(message) = messages.next().await {
+            if let tokio_postgres::AsyncMessage::Notification(notification) = message? {
+                log::info!("NOTIFY {NOTIFY_CHANNEL}: {}", notification.payload());
+                // the receiver is dropped only at the end of listen()
+                let _ = sender.unbounded_send(());
+            }
+        }
+        Ok::<(), tokio_postgres::Error>(())
+    });
+    client
+        .batch_execute(&format!("LISTEN {NOTIFY_CHANNEL};"))
+        .await?;
+    // the changes while the listener was disconnected are not lost
+    refresh_and_log(app_state).await;
+    // the sender is dropped when the connection is closed
+    while receiver.next().await.is_some() {
+        actix_web::rt::time::sleep(NOTIFY_DEBOUNCE).await;
+        while receiver.try_recv().is_ok() {}
+        refresh_and_log(app_state).await;
+    }
+    drop(client);
+    match connection_task.await {
+        Ok(result) => result,
+        // the task is never cancelled
+        Err(_join_error) => Ok(()),
+    }
+}
+
+/// the listener has nobody to return the error to
+async fn refresh_and_log(app_state: &DataAppState) {
+    if let Err(err) = app_state.sql_metadata.refresh(&app_state.db_pool).await {
+        log::error!("Sql metadata refresh failed: {err}");
+    }
+}
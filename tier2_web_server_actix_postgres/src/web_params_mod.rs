@@ -3,6 +3,7 @@
 use std::collections::HashMap;
 
 use crate::error_mod::{file_line_column, LibError};
+use crate::postgres_type_mod::{PostgresFieldType, PostgresInputType, PostgresValue};
 type WebForm = actix_web::web::Form<Vec<(String, String)>>;
 type WebQuery = actix_web::web::Query<Vec<(String, String)>>;
 
@@ -16,16 +17,28 @@ pub struct WebParams(pub HashMap<String, String>);
 impl WebParams {
     /// get WebParams from POST(form) if exists or else GET(web query)  
     /// If Post(form) exists, then GET(web query) is ignored.  
+    /// The data modifying request must be POST with the csrf token.  
+    /// The token is removed from WebParams, because it is not a param of the sql function.  
     /// track_caller decoration makes Location::caller() return the caller location  
     /// for meaningful source code location of the actual error  
     #[track_caller]
-    pub fn from_actix(query: &WebQuery, form: &Option<WebForm>) -> WebParams {
-        if let Some(form) = form {
+    pub fn from_actix(
+        req: &actix_web::HttpRequest,
+        query: &WebQuery,
+        form: &Option<WebForm>,
+        is_data_modifying: bool,
+    ) -> Result<WebParams, LibError> {
+        let mut web_params = if let Some(form) = form {
             // into_iter() consumes the vector. The vector cannot be used after calling this.
             WebParams(form.0.clone().into_iter().collect())
         } else {
             WebParams(query.0.clone().into_iter().collect())
+        };
+        let csrf_token = web_params.0.remove(crate::csrf_mod::CSRF_TOKEN_NAME);
+        if is_data_modifying {
+            crate::csrf_mod::verify_csrf_token(req, csrf_token.as_deref())?;
         }
+        Ok(web_params)
     }
 
     /// data from WebParams as &str  
@@ -38,7 +51,7 @@ impl WebParams {
             .ok_or(LibError::GetStrFromWebParams {
                 user_friendly: param_name.to_string(),
                 developer_friendly: format!("{:?}", self.0),
-                source_line_column: file_line_column(&std::panic::Location::caller()),
+                source_line_column: file_line_column(std::panic::Location::caller()),
             })?;
 
         Ok(value)
@@ -47,13 +60,183 @@ impl WebParams {
     /// data from WebParams as i32  
     #[track_caller]
     pub fn get_i32(&self, param_name: &str) -> Result<i32, LibError> {
-        let value = self.get_str(param_name)?.parse::<i32>().map_err(|_err| {
-            LibError::GetI32FromWebParams {
+        let value = self
+            .get_str(param_name)?
+            .trim()
+            .parse::<i32>()
+            .map_err(|_err| LibError::GetI32FromWebParams {
                 user_friendly: param_name.to_string(),
                 developer_friendly: format!("{:?}", self.0),
-                source_line_column: file_line_column(&std::panic::Location::caller()),
+                source_line_column: file_line_column(std::panic::Location::caller()),
+            })?;
+        Ok(value)
+    }
+
+    /// data from WebParams as positive i64  
+    /// If the param does not exist or is empty, returns the default value.  
+    #[track_caller]
+    pub fn get_positive_i64_or_default(
+        &self,
+        param_name: &str,
+        default: i64,
+    ) -> Result<i64, LibError> {
+        match self.0.get(param_name) {
+            None => Ok(default),
+            Some(value) if value.is_empty() => Ok(default),
+            Some(value) => match value.parse::<i64>() {
+                Ok(value) if value > 0 => Ok(value),
+                _ => Err(LibError::InvalidWebParam {
+                    user_friendly: format!("{param_name} must be a positive integer"),
+                    developer_friendly: format!("{:?}", self.0),
+                    source_line_column: file_line_column(std::panic::Location::caller()),
+                }),
+            },
+        }
+    }
+
+    /// data from WebParams cast to the data type of the view field  
+    /// It is used for the params in the WHERE clause.  
+    #[track_caller]
+    pub fn get_postgres_value_for_field(
+        &self,
+        param_name: &str,
+        field_type: &PostgresFieldType,
+    ) -> Result<PostgresValue, LibError> {
+        self.parse_postgres_value(param_name, &field_type.input_type())
+    }
+
+    /// data from WebParams cast to the data type of the function input param  
+    /// The empty string is NULL for input types that are not text.  
+    #[track_caller]
+    pub fn get_postgres_value_for_input(
+        &self,
+        param_name: &str,
+        input_type: &PostgresInputType,
+    ) -> Result<PostgresValue, LibError> {
+        if self.get_str(param_name)?.is_empty() && input_type.empty_string_is_null() {
+            return Ok(input_type.null_value());
+        }
+        self.parse_postgres_value(param_name, input_type)
+    }
+
+    /// parse the string from WebParams to the rust type for this postgres data type  
+    #[track_caller]
+    fn parse_postgres_value(
+        &self,
+        param_name: &str,
+        input_type: &PostgresInputType,
+    ) -> Result<PostgresValue, LibError> {
+        let value = match input_type {
+            PostgresInputType::Smallint => {
+                PostgresValue::I16(Some(self.parse(param_name, input_type)?))
+            }
+            PostgresInputType::Integer => PostgresValue::I32(Some(self.get_i32(param_name)?)),
+            PostgresInputType::Bigint => {
+                PostgresValue::I64(Some(self.parse(param_name, input_type)?))
+            }
+            PostgresInputType::Real => {
+                PostgresValue::F32(Some(self.parse(param_name, input_type)?))
+            }
+            PostgresInputType::DoublePrecision => {
+                PostgresValue::F64(Some(self.parse(param_name, input_type)?))
+            }
+            PostgresInputType::Numeric => {
+                PostgresValue::Decimal(Some(self.parse(param_name, input_type)?))
+            }
+            PostgresInputType::Boolean => PostgresValue::Bool(Some(self.get_bool(param_name)?)),
+            PostgresInputType::Character | PostgresInputType::Text => {
+                PostgresValue::String(Some(self.get_str(param_name)?.to_string()))
+            }
+            PostgresInputType::Date => {
+                PostgresValue::Date(Some(self.parse(param_name, input_type)?))
+            }
+            PostgresInputType::Timestamp => {
+                PostgresValue::Timestamp(Some(self.get_timestamp(param_name)?))
             }
-        })?;
+            PostgresInputType::TimestampTz => {
+                PostgresValue::TimestampTz(Some(self.get_timestamptz(param_name)?))
+            }
+            PostgresInputType::Uuid => {
+                PostgresValue::Uuid(Some(self.parse(param_name, input_type)?))
+            }
+            PostgresInputType::Json | PostgresInputType::Jsonb => {
+                PostgresValue::Json(Some(self.parse(param_name, input_type)?))
+            }
+        };
         Ok(value)
     }
+
+    /// data from WebParams parsed with FromStr  
+    #[track_caller]
+    fn parse<T: std::str::FromStr>(
+        &self,
+        param_name: &str,
+        input_type: &PostgresInputType,
+    ) -> Result<T, LibError> {
+        self.get_str(param_name)?
+            .trim()
+            .parse::<T>()
+            .map_err(|_err| self.invalid_web_param(param_name, input_type.as_ref()))
+    }
+
+    /// data from WebParams as bool  
+    /// html checkbox sends "on" when it is checked  
+    #[track_caller]
+    pub fn get_bool(&self, param_name: &str) -> Result<bool, LibError> {
+        match self.get_str(param_name)?.trim().to_lowercase().as_str() {
+            "true" | "t" | "1" | "on" | "yes" => Ok(true),
+            "false" | "f" | "0" | "off" | "no" => Ok(false),
+            _ => Err(self.invalid_web_param(param_name, "boolean")),
+        }
+    }
+
+    /// data from WebParams as timestamp without time zone  
+    /// html input datetime-local sends "2022-10-10T10:10" without seconds  
+    #[track_caller]
+    pub fn get_timestamp(&self, param_name: &str) -> Result<chrono::NaiveDateTime, LibError> {
+        let value = self.get_str(param_name)?.trim();
+        parse_timestamp(value).ok_or_else(|| self.invalid_web_param(param_name, "timestamp"))
+    }
+
+    /// data from WebParams as timestamp with time zone  
+    /// the timestamp without time zone is in UTC  
+    /// the rendered value "2022-10-10 10:10:10 UTC" is accepted, so the edited row can be saved  
+    #[track_caller]
+    pub fn get_timestamptz(
+        &self,
+        param_name: &str,
+    ) -> Result<chrono::DateTime<chrono::Utc>, LibError> {
+        let value = self.get_str(param_name)?.trim();
+        match chrono::DateTime::parse_from_rfc3339(value) {
+            Ok(value) => Ok(value.with_timezone(&chrono::Utc)),
+            Err(_err) => {
+                let value = value.strip_suffix(" UTC").unwrap_or(value).trim_end();
+                let value = parse_timestamp(value)
+                    .ok_or_else(|| self.invalid_web_param(param_name, "timestamptz"))?;
+                Ok(chrono::TimeZone::from_utc_datetime(&chrono::Utc, &value))
+            }
+        }
+    }
+
+    /// error for values that cannot be parsed to the data type  
+    #[track_caller]
+    pub fn invalid_web_param(&self, param_name: &str, type_name: &str) -> LibError {
+        LibError::InvalidWebParam {
+            user_friendly: format!("{param_name} is not {type_name}"),
+            developer_friendly: format!("{:?}", self.0),
+            source_line_column: file_line_column(std::panic::Location::caller()),
+        }
+    }
+}
+
+/// the formats of html input datetime-local and of the rendered timestamp
+fn parse_timestamp(value: &str) -> Option<chrono::NaiveDateTime> {
+    [
+        "%Y-%m-%dT%H:%M:%S%.f",
+        "%Y-%m-%dT%H:%M",
+        "%Y-%m-%d %H:%M:%S%.f",
+        "%Y-%m-%d %H:%M",
+    ]
+    .iter()
+    .find_map(|format| chrono::NaiveDateTime::parse_from_str(value, format).ok())
 }
@@ -2,52 +2,78 @@
 
 // type aliases: for less verbose types and better readability of the code
 
-use crate::actix_mod::{DataAppState, ResultResponse, WebForm, WebQuery};
+use crate::actix_mod::{DataAppState, ResultResponse, WebForm, WebQuery, WebRequest};
+use crate::server_side_import_mod::ServerSideImport;
 use crate::server_side_multi_row_mod::ServerSideMultiRow;
 use crate::server_side_single_row_mod::ServerSideSingleRow;
 use actix_web::web::resource;
 use actix_web::web::to;
+use actix_web::web::{get, post};
 
-const SCOPE: &'static str = "webpage_hits";
+const SCOPE: &str = "webpage_hits";
+/// The where statement is constructed only for existing parameters, because efficiency.
+/// The list and the export use the same filters.
+const WEBPAGE_HITS_LIST_WHERE_CLAUSE: [&str; 3] = [
+    "webpage like {f_like_webpage}",
+    "hit_count > {f_gt_hit_count}",
+    "hit_count < {f_lt_hit_count}",
+];
 
 /// scoped actix routing near the implementation code
 /// scope is already "/webpage_hits_admin/webpage_hits"
 pub fn config_route_webpage_hits(cfg: &mut actix_web::web::ServiceConfig) {
     cfg.service(resource("/webpage_hits_list").route(to(webpage_hits_list)))
+        .service(resource("/webpage_hits_list_export").route(to(webpage_hits_list_export)))
         .service(resource("/webpage_hits_new").route(to(webpage_hits_new)))
         .service(resource("/webpage_hits_edit").route(to(webpage_hits_edit)))
         .service(resource("/webpage_hits_insert").route(to(webpage_hits_insert)))
         .service(resource("/webpage_hits_show").route(to(webpage_hits_show)))
         .service(resource("/webpage_hits_update").route(to(webpage_hits_update)))
-        .service(resource("/webpage_hits_delete").route(to(webpage_hits_delete)));
+        .service(resource("/webpage_hits_delete").route(to(webpage_hits_delete)))
+        .service(
+            resource("/webpage_hits_import")
+                .route(get().to(webpage_hits_import_form))
+                .route(post().to(webpage_hits_import)),
+        );
 }
 
 /// CRUD - read (list all webpages and counts) with simple filter and order_by
 #[function_name::named]
 pub async fn webpage_hits_list(
     app_state: DataAppState,
+    req: WebRequest,
     query: WebQuery,
     form: Option<WebForm>,
 ) -> ResultResponse {
-    let mut ssmr = ServerSideMultiRow::new(&app_state, SCOPE, function_name!(), &query, &form);
-    // The where statement is constructed only for existing parameters, because efficiency.
-    ssmr.where_clause = vec![
-        "webpage like {f_like_webpage}",
-        "hit_counter > {f_gt_hit_counter}",
-        "hit_counter < {f_lt_hit_counter}",
-    ];
-
+    let mut ssmr =
+        ServerSideMultiRow::new(&app_state, &req, SCOPE, function_name!(), &query, &form)?;
+    ssmr.where_clause = WEBPAGE_HITS_LIST_WHERE_CLAUSE.to_vec();
     ssmr.run_multi_row_sql_and_process_html().await
 }
 
+/// export all webpages and counts as csv, tsv or excel with the same filter and order_by as the list
+pub async fn webpage_hits_list_export(
+    app_state: DataAppState,
+    req: WebRequest,
+    query: WebQuery,
+    form: Option<WebForm>,
+) -> ResultResponse {
+    let mut ssmr =
+        ServerSideMultiRow::new(&app_state, &req, SCOPE, "webpage_hits_list", &query, &form)?;
+    ssmr.where_clause = WEBPAGE_HITS_LIST_WHERE_CLAUSE.to_vec();
+    ssmr.run_multi_row_sql_and_export().await
+}
+
 /// UI - new record
 #[function_name::named]
 pub async fn webpage_hits_new(
     app_state: DataAppState,
+    req: WebRequest,
     query: WebQuery,
     form: Option<WebForm>,
 ) -> ResultResponse {
-    let mut sssr = ServerSideSingleRow::new(&app_state, SCOPE, function_name!(), &query, &form);
+    let mut sssr =
+        ServerSideSingleRow::new(&app_state, &req, SCOPE, function_name!(), &query, &form)?;
     sssr.run_single_row_sql_and_process_html().await
 }
 
@@ -55,10 +81,12 @@ pub async fn webpage_hits_new(
 #[function_name::named]
 pub async fn webpage_hits_edit(
     app_state: DataAppState,
+    req: WebRequest,
     query: WebQuery,
     form: Option<WebForm>,
 ) -> ResultResponse {
-    let mut sssr = ServerSideSingleRow::new(&app_state, SCOPE, function_name!(), &query, &form);
+    let mut sssr =
+        ServerSideSingleRow::new(&app_state, &req, SCOPE, function_name!(), &query, &form)?;
     sssr.run_single_row_sql_and_process_html().await
 }
 
@@ -66,10 +94,12 @@ pub async fn webpage_hits_edit(
 #[function_name::named]
 pub async fn webpage_hits_insert(
     app_state: DataAppState,
+    req: WebRequest,
     query: WebQuery,
     form: Option<WebForm>,
 ) -> ResultResponse {
-    let mut sssr = ServerSideSingleRow::new(&app_state, SCOPE, function_name!(), &query, &form);
+    let mut sssr =
+        ServerSideSingleRow::new(&app_state, &req, SCOPE, function_name!(), &query, &form)?;
     sssr.run_single_row_sql_and_process_html().await
 }
 
@@ -77,10 +107,12 @@ pub async fn webpage_hits_insert(
 #[function_name::named]
 pub async fn webpage_hits_show(
     app_state: DataAppState,
+    req: WebRequest,
     query: WebQuery,
     form: Option<WebForm>,
 ) -> ResultResponse {
-    let mut sssr = ServerSideSingleRow::new(&app_state, SCOPE, function_name!(), &query, &form);
+    let mut sssr =
+        ServerSideSingleRow::new(&app_state, &req, SCOPE, function_name!(), &query, &form)?;
     sssr.run_single_row_sql_and_process_html().await
 }
 
@@ -88,10 +120,12 @@ pub async fn webpage_hits_show(
 #[function_name::named]
 pub async fn webpage_hits_update(
     app_state: DataAppState,
+    req: WebRequest,
     query: WebQuery,
     form: Option<WebForm>,
 ) -> ResultResponse {
-    let mut sssr = ServerSideSingleRow::new(&app_state, SCOPE, function_name!(), &query, &form);
+    let mut sssr =
+        ServerSideSingleRow::new(&app_state, &req, SCOPE, function_name!(), &query, &form)?;
     sssr.run_single_row_sql_and_process_html().await
 }
 
@@ -99,9 +133,27 @@ pub async fn webpage_hits_update(
 #[function_name::named]
 pub async fn webpage_hits_delete(
     app_state: DataAppState,
+    req: WebRequest,
     query: WebQuery,
     form: Option<WebForm>,
 ) -> ResultResponse {
-    let mut sssr = ServerSideSingleRow::new(&app_state, SCOPE, function_name!(), &query, &form);
+    let mut sssr =
+        ServerSideSingleRow::new(&app_state, &req, SCOPE, function_name!(), &query, &form)?;
     sssr.run_single_row_sql_and_process_html().await
 }
+
+/// UI - upload form for the csv import
+pub async fn webpage_hits_import_form(app_state: DataAppState, req: WebRequest) -> ResultResponse {
+    let ssi = ServerSideImport::new(&app_state, &req, SCOPE, "webpage_hits_insert")?;
+    ssi.run_import_form()
+}
+
+/// CRUD - create(insert) all the rows from the uploaded csv file
+pub async fn webpage_hits_import(
+    app_state: DataAppState,
+    req: WebRequest,
+    payload: actix_multipart::Multipart,
+) -> ResultResponse {
+    let ssi = ServerSideImport::new(&app_state, &req, SCOPE, "webpage_hits_insert")?;
+    ssi.run_import_and_process_html(payload).await
+}
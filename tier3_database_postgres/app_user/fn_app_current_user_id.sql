@@ -0,0 +1,10 @@
+create or replace function public.app_current_user_id()
+returns integer
+language 'sql'
+stable
+as $body$
+-- the web server sets the logged in user at the start of every transaction:
+-- SELECT set_config('app.user_id', '1', true);
+-- returns NULL outside of the web server, for example in psql
+select nullif(current_setting('app.user_id', true), '')::integer;
+$body$;
@@ -0,0 +1,15 @@
+create or replace function public.app_user_login_get(
+_user_name varchar(100))
+returns table(id integer, password_hash varchar(200)) 
+language 'plpgsql'
+as $body$
+declare
+begin
+
+return query 
+select u.id, u.password_hash
+from app_user u
+where u.user_name=_user_name;
+
+end; 
+$body$;
@@ -0,0 +1,16 @@
+create or replace function public.app_user_session_delete(
+_session_id varchar(64))
+returns table(deleted_rows integer) 
+language 'plpgsql'
+as $body$
+declare
+begin
+
+delete from app_user_session s
+where s.session_id = _session_id;
+
+return query 
+select 1 as deleted_rows;
+
+end; 
+$body$;
@@ -0,0 +1,26 @@
+-- the return type has changed, so the function must be dropped first
+drop function if exists public.app_user_session_get(varchar);
+
+create or replace function public.app_user_session_get(
+_session_id varchar(64))
+returns table(app_user_id integer, user_name varchar(100), role_names varchar(100)[]) 
+language 'plpgsql'
+as $body$
+declare
+begin
+
+return query 
+select u.id, u.user_name,
+array(
+    select r.role_name 
+    from app_user_role ur
+    join app_role r on r.id = ur.app_role_id
+    where ur.app_user_id = u.id
+)::varchar(100)[]
+from app_user_session s
+join app_user u on u.id = s.app_user_id
+where s.session_id=_session_id
+and s.expires_at > now();
+
+end; 
+$body$;
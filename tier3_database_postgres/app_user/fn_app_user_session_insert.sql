@@ -0,0 +1,24 @@
+create or replace function public.app_user_session_insert(
+_session_id varchar(64),
+_app_user_id integer,
+_session_hours integer)
+returns table(session_id varchar(64), expires_at timestamp with time zone) 
+language 'plpgsql'
+as $body$
+declare
+begin
+
+-- the expired sessions are not needed anymore
+delete from app_user_session s
+where s.expires_at < now();
+
+insert into app_user_session (session_id, app_user_id, expires_at)
+values (_session_id, _app_user_id, now() + make_interval(hours => _session_hours));
+
+return query 
+select s.session_id, s.expires_at
+from app_user_session s
+where s.session_id=_session_id;
+
+end; 
+$body$;
@@ -0,0 +1,20 @@
+create or replace function public.app_role_permission_notify()
+returns trigger
+language plpgsql
+as $$
+-- the web server caches the role permissions and reloads them after this notification
+begin
+    perform pg_notify('sql_metadata_changed', tg_table_name);
+    return null;
+end;
+$$;
+
+drop trigger if exists app_role_permission_notify on public.app_role_permission;
+create trigger app_role_permission_notify
+after insert or update or delete or truncate on public.app_role_permission
+for each statement execute function public.app_role_permission_notify();
+
+drop trigger if exists app_role_notify on public.app_role;
+create trigger app_role_notify
+after insert or update or delete or truncate on public.app_role
+for each statement execute function public.app_role_permission_notify();
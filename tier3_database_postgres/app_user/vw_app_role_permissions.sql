@@ -0,0 +1,8 @@
+create or replace view public.app_role_permissions
+as
+-- select * from app_role_permissions;
+-- the web app reads it once on start and caches it
+
+select r.role_name, p.object_name
+from app_role_permission p
+join app_role r on r.id = p.app_role_id;
@@ -0,0 +1,20 @@
+create or replace function public.audit_log_insert(
+_app_user_id integer,
+_user_name varchar(100),
+_function_name varchar(100),
+_params jsonb,
+_outcome varchar(20),
+_error_message text)
+returns table(id bigint) 
+language 'plpgsql'
+as $body$
+declare
+begin
+
+return query 
+insert into audit_log (app_user_id, user_name, function_name, params, outcome, error_message)
+values (_app_user_id, _user_name, _function_name, _params, _outcome, _error_message)
+returning audit_log.id;
+
+end; 
+$body$;
@@ -0,0 +1,9 @@
+create or replace view public.audit_log_list
+as
+-- select * from audit_log_list;
+-- the params are text, so they can be filtered with like
+
+select a.id, a.created_at, a.user_name, a.function_name, a.params::text as params, 
+a.outcome, a.error_message
+from audit_log a
+order by a.id desc;
@@ -0,0 +1,9 @@
+-- roles of the admin web app users
+-- the permissions to call sql functions and read views are granted to roles, not to users
+CREATE TABLE IF NOT EXISTS public.app_role
+(
+    id SERIAL,
+    role_name varchar(100) COLLATE pg_catalog."default" NOT NULL,
+    CONSTRAINT app_role_pkey PRIMARY KEY (id),
+    CONSTRAINT app_role_uniq_role_name UNIQUE (role_name)
+)
@@ -0,0 +1,19 @@
+-- the role can call the sql function or read the view with this name
+-- the object_name '*' allows all functions and views
+-- insert into app_role(role_name) values ('admin'), ('reader');
+-- insert into app_role_permission(app_role_id, object_name) 
+-- select id, '*' from app_role where role_name='admin'
+-- union all
+-- select id, 'webpage_hits_list' from app_role where role_name='reader'
+-- union all
+-- select id, 'webpage_hits_show' from app_role where role_name='reader';
+CREATE TABLE IF NOT EXISTS public.app_role_permission
+(
+    app_role_id integer NOT NULL,
+    object_name varchar(100) COLLATE pg_catalog."default" NOT NULL,
+    CONSTRAINT app_role_permission_pkey PRIMARY KEY (app_role_id, object_name),
+    CONSTRAINT app_role FOREIGN KEY (app_role_id)
+        REFERENCES public.app_role (id) MATCH SIMPLE
+        ON UPDATE NO ACTION
+        ON DELETE CASCADE
+)
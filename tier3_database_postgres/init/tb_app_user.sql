@@ -0,0 +1,12 @@
+-- users of the admin web app
+-- the password is never stored, only the argon2 hash in the PHC string format: $argon2id$v=19$...
+-- create the hash with: echo -n "password" | webpage_hits_admin hash_password
+-- insert into app_user(user_name, password_hash) values ('admin', '$argon2id$v=19$...');
+CREATE TABLE IF NOT EXISTS public.app_user
+(
+    id SERIAL,
+    user_name varchar(100) COLLATE pg_catalog."default" NOT NULL,
+    password_hash varchar(200) COLLATE pg_catalog."default" NOT NULL,
+    CONSTRAINT app_user_pkey PRIMARY KEY (id),
+    CONSTRAINT app_user_uniq_user_name UNIQUE (user_name)
+)
@@ -0,0 +1,15 @@
+-- a user can have many roles
+CREATE TABLE IF NOT EXISTS public.app_user_role
+(
+    app_user_id integer NOT NULL,
+    app_role_id integer NOT NULL,
+    CONSTRAINT app_user_role_pkey PRIMARY KEY (app_user_id, app_role_id),
+    CONSTRAINT app_user FOREIGN KEY (app_user_id)
+        REFERENCES public.app_user (id) MATCH SIMPLE
+        ON UPDATE NO ACTION
+        ON DELETE CASCADE,
+    CONSTRAINT app_role FOREIGN KEY (app_role_id)
+        REFERENCES public.app_role (id) MATCH SIMPLE
+        ON UPDATE NO ACTION
+        ON DELETE CASCADE
+)
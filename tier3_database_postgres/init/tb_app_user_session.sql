@@ -0,0 +1,12 @@
+-- login sessions: the session_id is the random value in the cookie
+CREATE TABLE IF NOT EXISTS public.app_user_session
+(
+    session_id varchar(64) COLLATE pg_catalog."default" NOT NULL,
+    app_user_id integer NOT NULL,
+    expires_at timestamp with time zone NOT NULL,
+    CONSTRAINT app_user_session_pkey PRIMARY KEY (session_id),
+    CONSTRAINT app_user FOREIGN KEY (app_user_id)
+        REFERENCES public.app_user (id) MATCH SIMPLE
+        ON UPDATE NO ACTION
+        ON DELETE CASCADE
+)
@@ -0,0 +1,19 @@
+-- every call of a data modifying sql function (insert, update, delete) from the web app
+-- the user_name is copied, so the log stays readable after the user is deleted
+-- outcome: success, error or rolled_back (the call succeeded, but the transaction was rolled back)
+CREATE TABLE IF NOT EXISTS public.audit_log
+(
+    id BIGSERIAL,
+    created_at timestamp with time zone NOT NULL DEFAULT now(),
+    app_user_id integer,
+    user_name varchar(100) COLLATE pg_catalog."default",
+    function_name varchar(100) COLLATE pg_catalog."default" NOT NULL,
+    params jsonb NOT NULL,
+    outcome varchar(20) COLLATE pg_catalog."default" NOT NULL,
+    error_message text COLLATE pg_catalog."default",
+    CONSTRAINT audit_log_pkey PRIMARY KEY (id),
+    CONSTRAINT app_user FOREIGN KEY (app_user_id)
+        REFERENCES public.app_user (id) MATCH SIMPLE
+        ON UPDATE NO ACTION
+        ON DELETE SET NULL
+)
@@ -0,0 +1,26 @@
+create or replace function public.sql_metadata_changed_notify()
+returns event_trigger
+language plpgsql
+as $$
+-- the web server listens to the channel sql_metadata_changed
+-- and reloads the cache of functions, views and role permissions
+-- the event triggers can be created only by a superuser
+begin
+    perform pg_notify('sql_metadata_changed', tg_tag);
+end;
+$$;
+
+drop event trigger if exists sql_metadata_changed_ddl;
+create event trigger sql_metadata_changed_ddl on ddl_command_end
+when tag in (
+    'CREATE FUNCTION', 'ALTER FUNCTION', 'DROP FUNCTION',
+    'CREATE VIEW', 'ALTER VIEW', 'DROP VIEW', 'ALTER TABLE'
+)
+execute function public.sql_metadata_changed_notify();
+
+-- drop table cascade and drop schema cascade drop also the views and functions
+drop event trigger if exists sql_metadata_changed_drop;
+create event trigger sql_metadata_changed_drop on sql_drop
+execute function public.sql_metadata_changed_notify();
+
+-- test it: listen sql_metadata_changed; create or replace view test_view as select 1 as x;
@@ -1,14 +1,43 @@
-create or replace view public.get_function_input_params
+-- the columns are different from the old version of the view
+drop view if exists public.get_function_input_params;
+
+create view public.get_function_input_params
 as
 -- select * from get_function_input_params ;
+-- one row for every argument of the function in the correct order
+-- the function without arguments has one row with NULL in the argument columns
 
 SELECT 
+n.nspname,
 p.proname, 
--- definition of arguments without defaults. 
--- returns string that must be parsed later.
--- _id integer, _webpage character varying, _hit_count integer
-pg_get_function_arguments(oid) AS args_def 
+-- the function can be called without the schema, if it is visible in the search_path
+pg_function_is_visible(p.oid) as is_visible,
+-- the types of the input params: integer, character varying, integer
+-- the overloads and the functions in different schemas have the same name
+oidvectortypes(p.proargtypes) as signature,
+a.arg_position::integer,
+-- the unnamed argument has an empty name
+a.arg_name::text,
+-- i: IN, o: OUT, b: INOUT, v: VARIADIC, t: TABLE
+a.arg_mode::text,
+a.arg_type_oid,
+-- only the last input arguments can have the DEFAULT clause
+a.arg_mode in ('i', 'b', 'v') and a.input_position > p.pronargs - p.pronargdefaults as has_default
 FROM pg_proc p 
+join pg_namespace n on n.oid = p.pronamespace
+-- proallargtypes and proargmodes are NULL, when all the arguments are IN
+left join lateral (
+    select 
+    t.arg_position,
+    t.arg_type_oid,
+    coalesce(p.proargnames[t.arg_position], '') as arg_name,
+    coalesce(p.proargmodes[t.arg_position], 'i') as arg_mode,
+    count(*) filter (where coalesce(p.proargmodes[t.arg_position], 'i') in ('i', 'b', 'v')) 
+        over (order by t.arg_position) as input_position
+    from unnest(coalesce(p.proallargtypes, p.proargtypes::oid[])) 
+        with ordinality as t(arg_type_oid, arg_position)
+) a on true
 -- don't show system functions
-where p.pronamespace not in (11,13161)
-order by p.proname;
+-- the oid of the system namespaces is not the same in every installation
+where n.nspname not in ('pg_catalog', 'information_schema')
+order by n.nspname, p.proname, p.oid, a.arg_position;
@@ -11,4 +11,7 @@ FROM pg_class c
 INNER JOIN pg_attribute a ON a.attrelid = c.oid
 INNER JOIN pg_type t ON t.oid = a.atttypid
 WHERE c.relkind = 'v'
-and c.relnamespace not in (11,13161)
\ No newline at end of file
+-- the oid of the system namespaces is not the same in every installation
+and c.relnamespace not in (
+    select n.oid from pg_namespace n where n.nspname in ('pg_catalog', 'information_schema')
+)
\ No newline at end of file
@@ -0,0 +1,44 @@
+-- sample row-level security policies for the tables webpage and hit_counter
+-- It is not part of the init scripts. The DBA can adapt it and run it.
+-- The web server sets app.user_id in every transaction and app_current_user_id() returns it.
+-- The web app connects as the owner of the tables, so the policies must be forced,
+-- else the owner bypasses them. A superuser or a role with BYPASSRLS always bypasses them.
+-- The policies apply also to the views and functions owned by the same user.
+
+-- every new webpage belongs to the user that inserted it
+alter table public.webpage add column if not exists app_user_id integer 
+    default app_current_user_id()
+    references public.app_user (id) on delete set null;
+
+alter table public.webpage enable row level security;
+alter table public.webpage force row level security;
+alter table public.hit_counter enable row level security;
+alter table public.hit_counter force row level security;
+
+-- every logged in user can read all the rows
+drop policy if exists webpage_select on public.webpage;
+create policy webpage_select on public.webpage for select
+using (app_current_user_id() is not null);
+
+drop policy if exists hit_counter_select on public.hit_counter;
+create policy hit_counter_select on public.hit_counter for select
+using (app_current_user_id() is not null);
+
+-- the user can modify only the own rows and the old rows without the owner
+drop policy if exists webpage_modify on public.webpage;
+create policy webpage_modify on public.webpage for all
+using (app_user_id is null or app_user_id = app_current_user_id())
+with check (app_current_user_id() is not null);
+
+drop policy if exists hit_counter_modify on public.hit_counter;
+create policy hit_counter_modify on public.hit_counter for all
+using (exists (
+    select 1 from public.webpage w 
+    where w.id = hit_counter.webpage_id 
+    and (w.app_user_id is null or w.app_user_id = app_current_user_id())
+))
+with check (app_current_user_id() is not null);
+
+-- to remove the policies:
+-- alter table public.webpage disable row level security;
+-- alter table public.hit_counter disable row level security;